@@ -1,3 +1,26 @@
+// Dependency versions surfaced by the diagnostics_versions command.
+const REPORTED_CRATES: &[&str] = &["tauri", "tauri-plugin-store", "tauri-plugin-updater", "serialport"];
+
+fn export_locked_versions() {
+  println!("cargo:rerun-if-changed=Cargo.lock");
+  let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+  let mut name: Option<&str> = None;
+  for line in lock.lines() {
+    if let Some(n) = line.strip_prefix("name = ") { name = Some(n.trim_matches('"')); continue; }
+    if let (Some(n), Some(v)) = (name.take(), line.strip_prefix("version = ")) {
+      if REPORTED_CRATES.contains(&n) {
+        println!("cargo:rustc-env=NCRS_VERSION_{}={}", n.to_uppercase().replace('-', "_"), v.trim_matches('"'));
+      }
+    }
+  }
+  // keep env! resolvable when a crate is missing from the lockfile
+  for n in REPORTED_CRATES {
+    let key = format!("NCRS_VERSION_{}", n.to_uppercase().replace('-', "_"));
+    if !lock.contains(&format!("name = \"{}\"", n)) { println!("cargo:rustc-env={}=unknown", key); }
+  }
+}
+
 fn main() {
+  export_locked_versions();
   tauri_build::build()
 }
@@ -0,0 +1,80 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use crate::utils::{emit_event, EventScope, EventSink};
+
+// Alert sounds are played by the webview's audio queue, so only the webview can see the output
+// devices; it reports their labels with `audio-outputs` on load and whenever the list changes.
+static OUTPUTS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// `audio-outputs` listener: `payload` is `{ "names": [..] }` from the audio queue.
+pub(crate) fn outputs_reported(payload: &str) {
+  let Ok(v) = serde_json::from_str::<Value>(payload) else { return };
+  let names = v.get("names").and_then(|a| a.as_array()).map(|a| {
+    a.iter().filter_map(|s| s.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+  }).unwrap_or_default();
+  *OUTPUTS.lock().unwrap_or_else(|e| e.into_inner()) = names;
+}
+
+/// Output devices the alert sound can be pinned to, by label, as last reported by the webview.
+#[tauri::command]
+pub fn list_audio_outputs() -> Vec<String> {
+  OUTPUTS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Pins alert playback to output `name` (None or empty: the system default): saves it as
+/// `masterSettings.audioOutput` and emits `audio-output-changed`, which the audio queue applies
+/// with `HTMLMediaElement.setSinkId`. A device that is gone clears the setting, so playback falls
+/// back to the default, and is returned as an error.
+#[tauri::command]
+pub fn set_audio_output(app: AppHandle, name: Option<String>) -> Result<(), String> {
+  pin_output(&app, name)
+}
+
+fn pin_output(app: &impl EventSink, name: Option<String>) -> Result<(), String> {
+  let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+  let gone = name.as_ref().is_some_and(|n| !list_audio_outputs().contains(n));
+  let kept = if gone { None } else { name.clone() };
+  save_audio_output(kept.as_deref())?;
+  emit_event(app, EventScope::Status, "audio-output-changed", &json!({ "name": kept }));
+  match name.filter(|_| gone) {
+    Some(name) => Err(format!("audio output {:?} not found; using the default output", name)),
+    None => Ok(()),
+  }
+}
+
+fn save_audio_output(name: Option<&str>) -> Result<(), String> {
+  let _guard = crate::config::lock_config();
+  let mut v = crate::config::read_config()?;
+  let settings = v.as_object_mut().ok_or("config.json is not an object")?
+    .entry("masterSettings").or_insert_with(|| json!({}))
+    .as_object_mut().ok_or("masterSettings is not an object")?;
+  match name {
+    Some(name) => { settings.insert("audioOutput".into(), json!(name)); }
+    None => { settings.remove("audioOutput"); }
+  }
+  crate::config::write_config(&v)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::read_config;
+  use crate::config::test_support::use_config;
+  use crate::utils::RecordingSink;
+
+  #[test]
+  fn missing_output_falls_back_to_the_default() {
+    let _config = use_config(json!({ "masterSettings": { "audioOutput": "Old speaker" }, "masterData": [] }));
+    outputs_reported(r#"{ "names": ["Built-in Audio", " Ward speaker "] }"#);
+    let sink = RecordingSink::default();
+    pin_output(&sink, Some("Ward speaker".into())).unwrap();
+    assert_eq!(read_config().unwrap()["masterSettings"]["audioOutput"], "Ward speaker");
+    assert!(pin_output(&sink, Some("Unplugged USB".into())).is_err());
+    assert!(read_config().unwrap()["masterSettings"].get("audioOutput").is_none());
+    let names: Vec<Value> = sink.payloads("audio-output-changed").iter().map(|p| p["name"].clone()).collect();
+    assert_eq!(names, vec![json!("Ward speaker"), Value::Null]);
+  }
+}
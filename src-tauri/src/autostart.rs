@@ -0,0 +1,52 @@
+use serde_json::json;
+use tauri::AppHandle;
+use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_store::StoreExt;
+
+// app-level preferences (autostart, tray language), apart from the serial store
+pub(crate) const APP_STORE: &str = "app.json";
+// passed by the OS launcher so a start after reboot can be told apart from a manual one
+pub(crate) const AUTOSTART_ARG: &str = "--autostart";
+
+fn save_choice(app: &AppHandle, enabled: bool) -> Result<(), String> {
+  let store = app.store(APP_STORE).map_err(|e| e.to_string())?;
+  store.set("autostart", json!(enabled));
+  store.save().map_err(|e| e.to_string())
+}
+
+/// Registers the app to start at login and remembers the choice.
+#[tauri::command]
+pub fn autostart_enable(app: AppHandle) -> Result<(), String> {
+  app.autolaunch().enable().map_err(|e| e.to_string())?;
+  save_choice(&app, true)
+}
+
+/// Removes the login registration and remembers the choice.
+#[tauri::command]
+pub fn autostart_disable(app: AppHandle) -> Result<(), String> {
+  app.autolaunch().disable().map_err(|e| e.to_string())?;
+  save_choice(&app, false)
+}
+
+/// Whether the OS currently has the app registered to start at login.
+#[tauri::command]
+pub fn autostart_is_enabled(app: AppHandle) -> Result<bool, String> {
+  app.autolaunch().is_enabled().map_err(|e| e.to_string())
+}
+
+/// Startup: brings the OS registration back in line with the stored choice, e.g. after an
+/// update or a profile reset dropped it. Nothing happens until the user has chosen once.
+pub fn reconcile(app: &AppHandle) {
+  let Some(wanted) = app.store(APP_STORE).ok().and_then(|s| s.get("autostart")).and_then(|v| v.as_bool()) else { return };
+  let manager = app.autolaunch();
+  let registered = match manager.is_enabled() {
+    Ok(r) => r,
+    Err(e) => { log::warn!("autostart state unavailable: {}", e); return; }
+  };
+  if registered == wanted { return; }
+  let result = if wanted { manager.enable() } else { manager.disable() };
+  match result {
+    Ok(()) => log::info!("autostart {} to match the saved setting", if wanted { "re-registered" } else { "removed" }),
+    Err(e) => log::warn!("autostart could not be reconciled: {}", e),
+  }
+}
@@ -0,0 +1,98 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+  net::{TcpListener, TcpStream},
+  sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc, Mutex},
+  time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+// events mirrored to hallway displays
+const BROADCAST_EVENTS: [&str; 2] = ["nurse-call", "nurse-call-response"];
+const DEFAULT_BROADCAST_HOST: &str = "127.0.0.1";
+// a stalled display is dropped instead of holding up the others
+const CLIENT_WRITE_TIMEOUT_MS: u64 = 1000;
+// a client that connects but never finishes the handshake is given up on after this
+const HANDSHAKE_TIMEOUT_MS: u64 = 3000;
+const ACCEPT_POLL_MS: u64 = 200;
+
+enum Msg {
+  // boxed: a WebSocket is far larger than the other variants
+  Client(Box<WebSocket<TcpStream>>),
+  Event(String),
+  Shutdown,
+}
+
+// set while the server runs; publish is a no-op otherwise
+static SENDER: Lazy<Mutex<Option<mpsc::Sender<Msg>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Setup: with `masterSettings.broadcastPort` set, serves a WebSocket on
+/// `broadcastHost:broadcastPort` (host defaults to 127.0.0.1; use the ward LAN address to
+/// reach other screens) that mirrors every nurse-call and nurse-call-response.
+pub fn start(v: &Value) -> Result<(), String> {
+  let settings = v.get("masterSettings");
+  let Some(port) = settings.and_then(|m| m.get("broadcastPort")).and_then(|n| n.as_u64()).filter(|p| *p > 0 && *p <= u16::MAX as u64) else { return Ok(()) };
+  let host = settings.and_then(|m| m.get("broadcastHost")).and_then(|s| s.as_str()).filter(|s| !s.is_empty()).unwrap_or(DEFAULT_BROADCAST_HOST);
+  let addr = format!("{}:{}", host, port);
+  let listener = TcpListener::bind(&addr).map_err(|e| format!("broadcast bind {}: {}", addr, e))?;
+  // polled so shutdown never waits on a blocking accept
+  listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+  let (tx, rx) = mpsc::channel::<Msg>();
+  let stopped = Arc::new(AtomicBool::new(false));
+
+  let stopped_c = stopped.clone();
+  std::thread::spawn(move || {
+    let mut clients: Vec<WebSocket<TcpStream>> = Vec::new();
+    while let Ok(msg) = rx.recv() {
+      match msg {
+        Msg::Client(ws) => clients.push(*ws),
+        // a failed send means the display went away
+        Msg::Event(text) => clients.retain_mut(|ws| ws.send(Message::text(text.clone())).is_ok()),
+        Msg::Shutdown => break,
+      }
+    }
+    stopped_c.store(true, Ordering::Relaxed);
+    for mut ws in clients { let _ = ws.close(None); let _ = ws.flush(); }
+  });
+
+  let accept_tx = tx.clone();
+  std::thread::spawn(move || {
+    while !stopped.load(Ordering::Relaxed) {
+      match listener.accept() {
+        Ok((stream, peer)) => {
+          let _ = stream.set_nonblocking(false);
+          let _ = stream.set_write_timeout(Some(Duration::from_millis(CLIENT_WRITE_TIMEOUT_MS)));
+          let _ = stream.set_read_timeout(Some(Duration::from_millis(HANDSHAKE_TIMEOUT_MS)));
+          // on its own thread, so a client that never sends its handshake can't hold up the next
+          let client_tx = accept_tx.clone();
+          std::thread::spawn(move || match tungstenite::accept(stream) {
+            Ok(ws) => { log::info!("broadcast client connected: {}", peer); let _ = client_tx.send(Msg::Client(Box::new(ws))); }
+            Err(e) => log::warn!("broadcast handshake with {} failed: {}", peer, e),
+          });
+        }
+        Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(ACCEPT_POLL_MS)),
+        Err(e) => { log::warn!("broadcast accept failed: {}", e); std::thread::sleep(Duration::from_millis(ACCEPT_POLL_MS)); }
+      }
+    }
+  });
+
+  *SENDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+  log::info!("broadcasting nurse calls on ws://{}", addr);
+  Ok(())
+}
+
+/// Called by emit_event: forwards broadcast events to every connected display as
+/// `{"event": <name>, "payload": <same payload the window receives>}`.
+pub(crate) fn publish<S: Serialize>(event: &str, payload: &S) {
+  if !BROADCAST_EVENTS.contains(&event) { return; }
+  let sender = SENDER.lock().unwrap_or_else(|e| e.into_inner());
+  let Some(tx) = sender.as_ref() else { return };
+  let text = json!({ "event": event, "payload": payload }).to_string();
+  let _ = tx.send(Msg::Event(text));
+}
+
+/// Exit hook: closes every client connection and stops accepting.
+pub(crate) fn stop() {
+  if let Some(tx) = SENDER.lock().unwrap_or_else(|e| e.into_inner()).take() { let _ = tx.send(Msg::Shutdown); }
+}
@@ -0,0 +1,67 @@
+use serde_json::{json, Value};
+use std::{
+  collections::HashMap,
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::{atomic::{AtomicBool, Ordering}, Mutex},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::{config_path, flush_config, lock_config, read_config, write_config};
+
+// follows masterSettings.callEventLog, refreshed by long_running_thread
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// one writer at a time so lines from the serial thread and commands never interleave
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+fn log_path() -> PathBuf {
+  let cfg_path = config_path();
+  Path::new(&cfg_path).parent().unwrap_or(Path::new(".")).join("call-events.jsonl")
+}
+
+/// Called from setup and `long_running_thread`: picks up `masterSettings.callEventLog`.
+pub(crate) fn refresh(v: &Value) {
+  let on = v.get("masterSettings").and_then(|m| m.get("callEventLog")).and_then(|b| b.as_bool()).unwrap_or(false);
+  ENABLED.store(on, Ordering::Relaxed);
+}
+
+/// Appends `{"type": kind, "ts": <epoch ms>, "record": <call record after the change>}` to
+/// `call-events.jsonl` next to config.json. The file is only ever appended to, so a crash that
+/// corrupts config.json leaves it intact; at worst the last line is cut short.
+pub(crate) fn append(kind: &str, rec: &Value) {
+  if !ENABLED.load(Ordering::Relaxed) { return; }
+  let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+  let line = format!("{}\n", json!({ "type": kind, "ts": ts, "record": rec }));
+  let path = log_path();
+  let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  let res = fs::OpenOptions::new().create(true).append(true).open(&path).and_then(|mut f| f.write_all(line.as_bytes()));
+  if let Err(e) = res { log::warn!("call event log write failed ({}): {}", path.display(), e); }
+}
+
+/// Recovery: replaces `callHistoryStorage` with the calls replayed from `call-events.jsonl`.
+/// Each call ends up in the state of its last logged event, in the order calls first appeared;
+/// unreadable lines (e.g. one cut short by a crash) are skipped. Returns the number of calls.
+#[tauri::command]
+pub fn rebuild_history_from_log() -> Result<usize, String> {
+  let path = log_path();
+  let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+  let mut order: Vec<i64> = Vec::new();
+  let mut latest: HashMap<i64, Value> = HashMap::new();
+  let mut skipped = 0;
+  for line in text.lines().filter(|l| !l.trim().is_empty()) {
+    let Ok(entry) = serde_json::from_str::<Value>(line) else { skipped += 1; continue };
+    let Some(rec) = entry.get("record").filter(|r| r.is_object()) else { skipped += 1; continue };
+    let Some(id) = rec.get("id").and_then(|n| n.as_i64()) else { skipped += 1; continue };
+    if latest.insert(id, rec.clone()).is_none() { order.push(id); }
+  }
+  if skipped > 0 { log::warn!("call event log: skipped {} unreadable lines", skipped); }
+  let calls: Vec<Value> = order.iter().filter_map(|id| latest.remove(id)).collect();
+  let count = calls.len();
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  v.as_object_mut().ok_or("config.json is not an object")?.insert("callHistoryStorage".into(), Value::Array(calls));
+  write_config(&v)?;
+  flush_config()?;
+  Ok(count)
+}
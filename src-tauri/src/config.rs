@@ -0,0 +1,737 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+use std::{fs, io::Write, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard}, time::{SystemTime, UNIX_EPOCH}};
+use tauri::{AppHandle, Manager, State};
+
+use crate::utils::{emit_event, EventScope};
+
+const EMPTY_CONFIG: &str = "{\n  \"schemaVersion\": 1,\n  \"callHistoryStorage\": []\n}";
+const DEFAULT_SIZE_WARN_BYTES: u64 = 5 * 1024 * 1024;
+const LARGE_WARN_INTERVAL_MS: u128 = 10 * 60 * 1000;
+// bump together with a new entry in MIGRATIONS
+pub(crate) const SCHEMA_VERSION: u64 = 1;
+// MIGRATIONS[n] upgrades a config from schemaVersion n to n + 1
+const MIGRATIONS: [fn(&mut Value); 1] = [migrate_v0_timestamps];
+
+// NCRS_CONFIG_PATH, read once at startup
+static ENV_CONFIG_PATH: Lazy<Option<String>> = Lazy::new(|| std::env::var("NCRS_CONFIG_PATH").ok().filter(|p| !p.trim().is_empty()));
+// the Mutex behind ConfigState; the serial worker and other code without an AppHandle read it
+// through config_path()
+static RESOLVED_PATH: Lazy<Arc<Mutex<PathBuf>>> = Lazy::new(|| Arc::new(Mutex::new(PathBuf::new())));
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+// epoch ms of the last `config-large` event
+static LAST_LARGE_WARN: Lazy<Mutex<u128>> = Lazy::new(|| Mutex::new(0));
+// top-level keys read_config had to repair, waiting for the one-time `config-normalized`
+static NORMALIZED_KEYS: Lazy<Mutex<Vec<&'static str>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static NORMALIZED_REPORTED: AtomicBool = AtomicBool::new(false);
+// the parsed config.json, shared by every reader; writes land here and are flushed by flush_loop
+static CACHE: Lazy<Mutex<Option<ConfigCache>>> = Lazy::new(|| Mutex::new(None));
+// how long a burst of writes may coalesce before hitting the disk
+const FLUSH_INTERVAL_MS: u64 = 250;
+
+struct ConfigCache {
+  path: String,
+  value: Value,
+  // file mtime when last read or flushed, to pick up edits made outside the app
+  modified: Option<SystemTime>,
+  dirty: bool,
+}
+
+impl ConfigCache {
+  fn flush(&mut self) -> Result<(), String> {
+    if !self.dirty { return Ok(()); }
+    write_atomic(&self.path, &serialize_config(&self.value)?)?;
+    self.modified = file_mtime(&self.path);
+    self.dirty = false;
+    Ok(())
+  }
+}
+
+fn file_mtime(path: &str) -> Option<SystemTime> { fs::metadata(path).and_then(|m| m.modified()).ok() }
+
+fn lock_cache() -> MutexGuard<'static, Option<ConfigCache>> { CACHE.lock().unwrap_or_else(|e| e.into_inner()) }
+
+// a pending write for a path that is no longer current still goes to its own file
+fn flush_if_other_path(cache: &mut Option<ConfigCache>, path: &str) {
+  if let Some(c) = cache.as_mut().filter(|c| c.path != path) {
+    if let Err(e) = c.flush() { log::warn!("config flush to {} failed: {}", c.path, e); }
+  }
+}
+
+/// The config.json path every reader and writer uses, managed on the builder. Empty until
+/// `init_config_path` resolves it during setup; `config_set_path` can repoint it afterwards.
+pub struct ConfigState(Arc<Mutex<PathBuf>>);
+
+impl Default for ConfigState {
+  fn default() -> Self { Self(Arc::clone(&RESOLVED_PATH)) }
+}
+
+impl ConfigState {
+  fn get(&self) -> PathBuf { self.0.lock().unwrap_or_else(|e| e.into_inner()).clone() }
+
+  fn set(&self, path: PathBuf) { *self.0.lock().unwrap_or_else(|e| e.into_inner()) = path; }
+}
+
+/// Resolved config.json path (see `ConfigState`), "config.json" before setup has run.
+pub(crate) fn config_path() -> String {
+  let path = RESOLVED_PATH.lock().unwrap_or_else(|e| e.into_inner()).clone();
+  if path.as_os_str().is_empty() { "config.json".to_string() } else { path.to_string_lossy().to_string() }
+}
+
+/// Called once from setup: resolves the path (`NCRS_CONFIG_PATH` env, else config.json under
+/// the app data dir, falling back to the resource dir) into `ConfigState` and creates an
+/// empty config there if none exists yet. Precedence: env > `config_set_path` > default.
+pub(crate) fn init_config_path(app: &AppHandle) -> Result<(), String> {
+  let path = match ENV_CONFIG_PATH.as_ref() {
+    Some(p) => PathBuf::from(p),
+    None => app.path().app_data_dir().or_else(|_| app.path().resource_dir()).map_err(|e| e.to_string())?.join("config.json"),
+  };
+  app.state::<ConfigState>().set(path);
+  ensure_config_file(&config_path())
+}
+
+fn ensure_config_file(path: &str) -> Result<(), String> {
+  if Path::new(path).is_file() { return Ok(()); }
+  if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+  }
+  write_atomic(path, EMPTY_CONFIG)
+}
+
+/// The config.json path every reader and writer currently uses.
+#[tauri::command]
+pub fn config_get_path(state: State<'_, ConfigState>) -> String { state.get().to_string_lossy().to_string() }
+
+/// Points the app at another config file until restart, creating it (with an empty
+/// history) if it doesn't exist. The path is checked by `check_config_target`; refused while
+/// `NCRS_CONFIG_PATH` is set.
+#[tauri::command]
+pub fn config_set_path(state: State<'_, ConfigState>, path: String) -> Result<String, String> {
+  if ENV_CONFIG_PATH.is_some() { return Err("NCRS_CONFIG_PATH is set and takes precedence".into()); }
+  let path = check_config_target(path.trim())?;
+  ensure_config_file(&path)?;
+  let _guard = lock_config();
+  flush_config()?;
+  state.set(PathBuf::from(&path));
+  Ok(path)
+}
+
+// A config_set_path target must be an absolute path to a .json file in an existing directory;
+// an existing file must already hold a JSON object, so the app is never pointed at (and later
+// overwrites) some unrelated file.
+fn check_config_target(path: &str) -> Result<String, String> {
+  if path.is_empty() { return Err("path is empty".into()); }
+  let p = Path::new(path);
+  if !p.is_absolute() { return Err(format!("{}: path must be absolute", path)); }
+  if !p.extension().is_some_and(|e| e.eq_ignore_ascii_case("json")) { return Err(format!("{}: not a .json file", path)); }
+  if !p.parent().is_some_and(|d| d.is_dir()) { return Err(format!("{}: directory does not exist", path)); }
+  if p.exists() {
+    let text = fs::read_to_string(p).map_err(|e| format!("{}: {}", path, e))?;
+    if !serde_json::from_str::<Value>(&text).is_ok_and(|v| v.is_object()) { return Err(format!("{}: not a config file (expected a JSON object)", path)); }
+  }
+  Ok(path.to_string())
+}
+
+/// Held across a read-modify-write of config.json so the serial worker and commands never
+/// overwrite each other's changes.
+pub(crate) fn lock_config() -> MutexGuard<'static, ()> {
+  CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The current config, from the cache when it is fresh. Unflushed writes are always included;
+/// otherwise a changed file mtime (a hand edit) reloads it from disk.
+pub(crate) fn read_config() -> Result<Value, String> {
+  let path = config_path();
+  let mut cache = lock_cache();
+  if let Some(c) = cache.as_ref().filter(|c| c.path == path) {
+    if c.dirty || file_mtime(&path) == c.modified { return Ok(c.value.clone()); }
+  }
+  flush_if_other_path(&mut cache, &path);
+  let modified = file_mtime(&path);
+  let cfg_text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+  let mut v: Value = serde_json::from_str(&cfg_text).map_err(|e| e.to_string())?;
+  let repaired = normalize_config(&mut v);
+  if !repaired.is_empty() && !NORMALIZED_REPORTED.load(Ordering::Relaxed) {
+    let mut keys = NORMALIZED_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    for key in repaired { if !keys.contains(&key) { keys.push(key); } }
+  }
+  *cache = Some(ConfigCache { path, value: v.clone(), modified, dirty: false });
+  Ok(v)
+}
+
+// Replaces a missing or wrongly typed callHistoryStorage/masterData (arrays) or masterSettings
+// (object) with an empty one, so write paths record instead of silently skipping. The file is
+// repaired on the next write. Returns the keys that were replaced.
+fn normalize_config(v: &mut Value) -> Vec<&'static str> {
+  let Some(obj) = v.as_object_mut() else { return Vec::new() };
+  let mut repaired = Vec::new();
+  for (key, want_array) in [("callHistoryStorage", true), ("masterData", true), ("masterSettings", false)] {
+    let ok = match obj.get(key) {
+      Some(Value::Array(_)) => want_array,
+      Some(Value::Object(_)) => !want_array,
+      _ => false,
+    };
+    if ok { continue; }
+    obj.insert(key.into(), if want_array { Value::Array(Vec::new()) } else { Value::Object(Default::default()) });
+    repaired.push(key);
+  }
+  repaired
+}
+
+// v0 -> v1: records that only carry the local `time` ("%H:%M:%S.%m-%d-%Y") get an RFC3339 `timestamp`
+fn migrate_v0_timestamps(v: &mut Value) {
+  use chrono::TimeZone;
+  let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) else { return };
+  for obj in arr.iter_mut().filter_map(|r| r.as_object_mut()) {
+    if obj.get("timestamp").and_then(|s| s.as_str()).is_some_and(|s| !s.is_empty()) { continue; }
+    let Some(time) = obj.get("time").and_then(|s| s.as_str()) else { continue };
+    let Ok(naive) = chrono::NaiveDateTime::parse_from_str(time, "%H:%M:%S%.f.%m-%d-%Y").or_else(|_| chrono::NaiveDateTime::parse_from_str(time, "%H:%M:%S.%m-%d-%Y")) else { continue };
+    let Some(local) = chrono::Local.from_local_datetime(&naive).earliest() else { continue };
+    let iso = local.with_timezone(&chrono::Utc).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    obj.insert("timestamp".into(), Value::String(iso));
+  }
+}
+
+/// Applies every migration from the file's `schemaVersion` (absent = 0) up to SCHEMA_VERSION,
+/// in order, and stamps the new version. Returns whether anything changed. Files from a newer
+/// build are left alone.
+pub(crate) fn migrate_config(v: &mut Value) -> bool {
+  if !v.is_object() { return false; }
+  let from = v.get("schemaVersion").and_then(|n| n.as_u64()).unwrap_or(0);
+  if from >= SCHEMA_VERSION { return false; }
+  for migrate in &MIGRATIONS[from as usize..] { migrate(v); }
+  v["schemaVersion"] = Value::from(SCHEMA_VERSION);
+  true
+}
+
+/// Startup: migrates config.json in place, writing it back (atomically) only when it changed.
+pub(crate) fn run_migrations() -> Result<Option<u64>, String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let from = v.get("schemaVersion").and_then(|n| n.as_u64()).unwrap_or(0);
+  if !migrate_config(&mut v) { return Ok(None); }
+  write_config(&v)?;
+  flush_config()?;
+  Ok(Some(from))
+}
+
+/// Called from `long_running_thread`: emits `config-normalized` with the repaired keys the
+/// first time read_config had to fix the file's shape.
+pub(crate) fn report_normalized(app: &AppHandle) {
+  let keys = std::mem::take(&mut *NORMALIZED_KEYS.lock().unwrap_or_else(|e| e.into_inner()));
+  if keys.is_empty() || NORMALIZED_REPORTED.swap(true, Ordering::Relaxed) { return; }
+  log::warn!("config.json was missing or had malformed {}; using empty defaults", keys.join(", "));
+  emit_event(app, EventScope::Status, "config-normalized", &serde_json::json!({ "keys": keys, "path": config_path() }));
+}
+
+/// Writes `text` to a sibling `<name>.tmp`, flushes it to disk and renames it over `path`,
+/// so a crash mid-write never leaves a truncated file behind.
+pub(crate) fn write_atomic(path: &str, text: &str) -> Result<(), String> {
+  let tmp = format!("{}.tmp", path);
+  let res = (|| -> std::io::Result<()> {
+    let mut f = fs::File::create(&tmp)?;
+    f.write_all(text.as_bytes())?;
+    f.sync_all()?;
+    // std::fs::rename replaces an existing target on Windows as well
+    fs::rename(&tmp, path)
+  })();
+  if res.is_err() && Path::new(&tmp).exists() { let _ = fs::remove_file(&tmp); }
+  res.map_err(|e| e.to_string())
+}
+
+// With `masterSettings.compactHistory` each callHistoryStorage record is written on one
+// compact line; everything else stays pretty-printed for hand editing. Default: all pretty.
+pub(crate) fn serialize_config(v: &Value) -> Result<String, String> {
+  let compact = v.get("masterSettings").and_then(|m| m.get("compactHistory")).and_then(|b| b.as_bool()).unwrap_or(false);
+  let Some(obj) = v.as_object().filter(|_| compact) else {
+    return serde_json::to_string_pretty(v).map_err(|e| e.to_string());
+  };
+  let mut fields = Vec::with_capacity(obj.len());
+  for (key, val) in obj {
+    let body = match val.as_array().filter(|_| key == "callHistoryStorage") {
+      Some(records) if !records.is_empty() => {
+        let lines = records.iter().map(serde_json::to_string).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+        format!("[\n    {}\n  ]", lines.join(",\n    "))
+      }
+      _ => serde_json::to_string_pretty(val).map_err(|e| e.to_string())?.replace('\n', "\n  "),
+    };
+    fields.push(format!("  {}: {}", Value::String(key.clone()), body));
+  }
+  Ok(format!("{{\n{}\n}}", fields.join(",\n")))
+}
+
+// Every config.json mutation goes through here: it updates the cache right away and marks it
+// dirty, and flush_loop writes it atomically, so a call storm costs one write per interval.
+pub(crate) fn write_config(v: &Value) -> Result<(), String> {
+  let path = config_path();
+  let mut cache = lock_cache();
+  flush_if_other_path(&mut cache, &path);
+  let modified = cache.as_ref().filter(|c| c.path == path).and_then(|c| c.modified);
+  *cache = Some(ConfigCache { path, value: v.clone(), modified, dirty: true });
+  Ok(())
+}
+
+/// Writes any pending config change to disk now (shutdown, path switch, tests of the file).
+pub(crate) fn flush_config() -> Result<(), String> {
+  match lock_cache().as_mut() {
+    Some(c) => c.flush(),
+    None => Ok(()),
+  }
+}
+
+/// Spawned from setup: flushes coalesced config writes every FLUSH_INTERVAL_MS. A failed
+/// flush stays dirty and is retried on the next round.
+pub async fn flush_loop() {
+  let mut failing = false;
+  loop {
+    tokio::time::sleep(std::time::Duration::from_millis(FLUSH_INTERVAL_MS)).await;
+    match flush_config() {
+      Ok(()) => failing = false,
+      // logged once per failure streak rather than four times a second
+      Err(e) => { if !failing { log::warn!("config write failed: {}", e); } failing = true; }
+    }
+  }
+}
+
+// JSON merge-patch: objects merge key by key, `null` removes a key, anything else replaces
+fn merge_patch(target: &mut Value, patch: &Value) {
+  match (target.as_object_mut(), patch.as_object()) {
+    (Some(t), Some(p)) => {
+      for (k, pv) in p {
+        if pv.is_null() { t.remove(k); continue; }
+        merge_patch(t.entry(k.clone()).or_insert(Value::Null), pv);
+      }
+    }
+    _ => *target = patch.clone(),
+  }
+}
+
+/// The whole config.json as the backend sees it: from the resolved path (created with an empty
+/// history if missing) and normalized, so the UI reads exactly what the serial code reads and
+/// writes. Write back with `update_config` (or `write_public_config` for a full replace).
+#[tauri::command]
+pub fn config_read() -> Result<Value, String> {
+  ensure_config_file(&config_path())?;
+  read_config()
+}
+
+/// Deep-merges a partial update into config.json and persists it atomically. Keys missing
+/// from `patch` (including `callHistoryStorage`) are left untouched. Returns the merged config.
+#[tauri::command]
+pub fn update_config(patch: Value) -> Result<Value, String> {
+  if !patch.is_object() { return Err("config patch must be a JSON object".into()); }
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  merge_patch(&mut v, &patch);
+  write_config(&v)?;
+  Ok(v)
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigWriteErrorKind {
+  InvalidJson,
+  MissingKeys,
+  DropsActiveCalls,
+  WriteFailed,
+}
+
+/// Why write_public_config refused a payload; sent to the frontend as the error value.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigWriteError {
+  kind: ConfigWriteErrorKind,
+  message: String,
+  // top-level keys that are missing or of the wrong type
+  invalid_keys: Vec<&'static str>,
+  // ids of non-completed calls the payload would remove
+  dropped_active_ids: Vec<i64>,
+}
+
+impl ConfigWriteError {
+  fn new(kind: ConfigWriteErrorKind, message: impl Into<String>) -> Self {
+    Self { kind, message: message.into(), invalid_keys: Vec::new(), dropped_active_ids: Vec::new() }
+  }
+}
+
+fn active_call_ids(v: &Value) -> Vec<i64> {
+  v.get("callHistoryStorage").and_then(|a| a.as_array()).into_iter().flatten()
+    .filter(|rec| crate::serial::is_open(rec))
+    .filter_map(|rec| rec.get("id").and_then(|n| n.as_i64()))
+    .collect()
+}
+
+/// Deprecated: prefer `update_config`, which merges a partial update.
+/// Replaces the whole config.json (at the same path the serial worker uses) with `text`.
+/// The payload must be a JSON object with `callHistoryStorage` and `masterData` arrays and a
+/// `masterSettings` object; a payload that would remove calls still open is refused unless
+/// `force` is set. Written through the cache and flushed atomically before returning.
+#[tauri::command]
+pub fn write_public_config(text: String, force: Option<bool>) -> Result<(), ConfigWriteError> {
+  use ConfigWriteErrorKind::*;
+  let v: Value = serde_json::from_str(&text).map_err(|e| ConfigWriteError::new(InvalidJson, format!("invalid config JSON: {}", e)))?;
+  let Some(obj) = v.as_object() else { return Err(ConfigWriteError::new(InvalidJson, "invalid config JSON: expected an object")) };
+  let invalid_keys: Vec<&'static str> = [("callHistoryStorage", true), ("masterData", true), ("masterSettings", false)]
+    .into_iter()
+    .filter(|(key, want_array)| !obj.get(*key).is_some_and(|x| if *want_array { x.is_array() } else { x.is_object() }))
+    .map(|(key, _)| key)
+    .collect();
+  if !invalid_keys.is_empty() {
+    let message = format!("missing or invalid keys: {}", invalid_keys.join(", "));
+    return Err(ConfigWriteError { invalid_keys, ..ConfigWriteError::new(MissingKeys, message) });
+  }
+  // through the cache, so a pending serial write can't be flushed over it afterwards
+  let _guard = lock_config();
+  if !force.unwrap_or(false) {
+    let current = read_config().map_err(|e| ConfigWriteError::new(WriteFailed, e))?;
+    let kept: Vec<i64> = obj["callHistoryStorage"].as_array().into_iter().flatten().filter_map(|rec| rec.get("id").and_then(|n| n.as_i64())).collect();
+    let dropped_active_ids: Vec<i64> = active_call_ids(&current).into_iter().filter(|id| !kept.contains(id)).collect();
+    if !dropped_active_ids.is_empty() {
+      let message = format!("would remove {} active call(s); pass force to overwrite", dropped_active_ids.len());
+      return Err(ConfigWriteError { dropped_active_ids, ..ConfigWriteError::new(DropsActiveCalls, message) });
+    }
+  }
+  write_config(&v).and_then(|_| flush_config()).map_err(|e| ConfigWriteError::new(WriteFailed, e))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigHealth {
+  size_bytes: u64,
+  record_count: usize,
+  active_count: usize,
+  warn_bytes: u64,
+  warning: bool,
+  // purging completed records older than this would bring the file back under `warn_bytes`
+  suggested_purge_before: Option<String>,
+}
+
+fn compute_health(v: &Value, size_bytes: u64) -> ConfigHealth {
+  let warn_bytes = v.get("masterSettings").and_then(|m| m.get("configSizeWarnBytes")).and_then(|n| n.as_u64()).unwrap_or(DEFAULT_SIZE_WARN_BYTES);
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let active_count = arr.iter().filter(|rec| crate::serial::is_open(rec)).count();
+  let warning = size_bytes > warn_bytes;
+  let mut suggested_purge_before = None;
+  if warning && !arr.is_empty() {
+    let per_record = (size_bytes / arr.len() as u64).max(1);
+    let remove = ((size_bytes - warn_bytes) / per_record + 1) as usize;
+    let mut completed: Vec<&str> = arr.iter()
+      .filter(|rec| !crate::serial::is_open(rec))
+      .filter_map(|rec| rec.get("timestamp").and_then(|s| s.as_str()))
+      .collect();
+    completed.sort();
+    suggested_purge_before = completed.get(remove).or(completed.last()).map(|s| s.to_string());
+  }
+  ConfigHealth { size_bytes, record_count: arr.len(), active_count, warn_bytes, warning, suggested_purge_before }
+}
+
+/// File size and record counts of config.json, flagged when it outgrows `configSizeWarnBytes`.
+#[tauri::command]
+pub fn config_health() -> Result<ConfigHealth, String> {
+  let size = fs::metadata(config_path()).map_err(|e| e.to_string())?.len();
+  Ok(compute_health(&read_config()?, size))
+}
+
+/// Called from `long_running_thread`; emits `config-large` at most every ten minutes.
+pub(crate) fn warn_if_large(app: &AppHandle, v: &Value) {
+  let Ok(meta) = fs::metadata(config_path()) else { return };
+  let health = compute_health(v, meta.len());
+  if !health.warning { return; }
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  let mut last = LAST_LARGE_WARN.lock().unwrap_or_else(|e| e.into_inner());
+  if now.saturating_sub(*last) < LARGE_WARN_INTERVAL_MS { return; }
+  *last = now;
+  emit_event(app, EventScope::Status, "config-large", &health);
+}
+
+/// Marks a masterData entry in or out of service without removing its room mapping.
+#[tauri::command]
+pub fn set_code_enabled(code: String, enabled: bool) -> Result<(), String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let entry = v.get_mut("masterData")
+    .and_then(|a| a.as_array_mut())
+    .and_then(|md| md.iter_mut().find(|r| r.get("charCode").and_then(|s| s.as_str()) == Some(code.as_str())))
+    .and_then(|r| r.as_object_mut())
+    .ok_or(format!("code {} is not in masterData", code))?;
+  entry.insert("enabled".into(), Value::Bool(enabled));
+  write_config(&v)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingFields {
+  index: usize,
+  char_code: String,
+  fields: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MasterDataReport {
+  valid: bool,
+  // charCodes mapped more than once; only the first mapping is ever used
+  duplicates: Vec<String>,
+  missing: Vec<MissingFields>,
+}
+
+pub(crate) fn check_master_data(v: &Value) -> MasterDataReport {
+  let mut seen = std::collections::HashSet::new();
+  let mut duplicates = Vec::new();
+  let mut missing = Vec::new();
+  let empty = Vec::new();
+  let md = v.get("masterData").and_then(|a| a.as_array()).unwrap_or(&empty);
+  for (index, r) in md.iter().enumerate() {
+    let char_code = r.get("charCode").and_then(|s| s.as_str()).unwrap_or("").trim().to_string();
+    let fields: Vec<&'static str> = ["charCode", "roomName", "bedName"]
+      .into_iter()
+      .filter(|key| r.get(*key).and_then(|s| s.as_str()).map(|s| s.trim().is_empty()).unwrap_or(true))
+      .collect();
+    if !fields.is_empty() { missing.push(MissingFields { index, char_code: char_code.clone(), fields }); }
+    if !char_code.is_empty() && !seen.insert(char_code.clone()) && !duplicates.contains(&char_code) {
+      duplicates.push(char_code);
+    }
+  }
+  MasterDataReport { valid: duplicates.is_empty() && missing.is_empty(), duplicates, missing }
+}
+
+impl MasterDataReport {
+  pub fn is_valid(&self) -> bool { self.valid }
+}
+
+/// Checks masterData for duplicate charCodes and entries missing charCode/roomName/bedName.
+#[tauri::command]
+pub fn validate_master_data() -> Result<MasterDataReport, String> {
+  Ok(check_master_data(&read_config()?))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteBench {
+  iterations: u32,
+  size_bytes: usize,
+  min_ms: f64,
+  avg_ms: f64,
+  max_ms: f64,
+}
+
+/// Debug builds only: times `iterations` write_config-style writes of the current config
+/// to a temp copy, so the real file and its history are never touched.
+#[tauri::command]
+pub fn bench_config_write(iterations: u32) -> Result<WriteBench, String> {
+  if !cfg!(debug_assertions) { return Err("bench_config_write is only available in debug builds".into()); }
+  let iterations = iterations.clamp(1, 1000);
+  let v = read_config()?;
+  let tmp = std::env::temp_dir().join("ncrs-bench-config.json");
+  let tmp = tmp.to_string_lossy().to_string();
+  let mut samples = Vec::with_capacity(iterations as usize);
+  let mut size_bytes = 0;
+  for _ in 0..iterations {
+    let started = std::time::Instant::now();
+    let text = serialize_config(&v)?;
+    write_atomic(&tmp, &text)?;
+    samples.push(started.elapsed().as_secs_f64() * 1000.0);
+    size_bytes = text.len();
+  }
+  let _ = fs::remove_file(&tmp);
+  let min_ms = samples.iter().cloned().fold(f64::INFINITY, f64::min);
+  let max_ms = samples.iter().cloned().fold(0.0, f64::max);
+  let avg_ms = samples.iter().sum::<f64>() / samples.len() as f64;
+  Ok(WriteBench { iterations, size_bytes, min_ms, avg_ms, max_ms })
+}
+
+/// Shared by every test module that goes through config.json.
+#[cfg(test)]
+pub(crate) mod test_support {
+  use super::*;
+
+  // the config path and cache are process-wide, so tests that use them take turns
+  static TURN: Mutex<()> = Mutex::new(());
+
+  /// Points the app at a fresh temp config.json holding `v` until the guard is dropped.
+  pub(crate) fn use_config(v: Value) -> MutexGuard<'static, ()> {
+    // it would take precedence and point the tests at a real file
+    assert!(ENV_CONFIG_PATH.is_none(), "unset NCRS_CONFIG_PATH to run the tests");
+    let turn = TURN.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!("ncrs-test-{}.json", std::process::id())).to_string_lossy().to_string();
+    fs::write(&path, v.to_string()).expect("write test config");
+    ConfigState::default().set(PathBuf::from(path));
+    // forget whatever the previous test left behind, flushed or not
+    *lock_cache() = None;
+    turn
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn valid() -> Value { json!({ "callHistoryStorage": [{ "id": 1 }], "masterData": [], "masterSettings": { "masterType": "Commax" } }) }
+
+  #[test]
+  fn well_formed_config_is_left_alone() {
+    let mut v = valid();
+    assert!(normalize_config(&mut v).is_empty());
+    assert_eq!(v, valid());
+  }
+
+  #[test]
+  fn missing_keys_are_added_empty() {
+    for key in ["callHistoryStorage", "masterData", "masterSettings"] {
+      let mut v = valid();
+      v.as_object_mut().unwrap().remove(key);
+      assert_eq!(normalize_config(&mut v), vec![key]);
+      let want = if key == "masterSettings" { json!({}) } else { json!([]) };
+      assert_eq!(v[key], want, "{}", key);
+    }
+  }
+
+  #[test]
+  fn wrongly_typed_keys_are_replaced() {
+    for (key, bad, want) in [
+      ("callHistoryStorage", json!({ "0": { "id": 1 } }), json!([])),
+      ("masterData", json!("101"), json!([])),
+      ("masterSettings", json!([]), json!({})),
+    ] {
+      let mut v = valid();
+      v[key] = bad;
+      assert_eq!(normalize_config(&mut v), vec![key]);
+      assert_eq!(v[key], want, "{}", key);
+    }
+  }
+
+  #[test]
+  fn other_keys_survive_normalization() {
+    let mut v = json!({ "schemaVersion": 1, "masterSettings": null });
+    assert_eq!(normalize_config(&mut v), vec!["callHistoryStorage", "masterData", "masterSettings"]);
+    assert_eq!(v["schemaVersion"], 1);
+  }
+
+  #[test]
+  fn read_config_returns_the_repaired_shape() {
+    let _config = test_support::use_config(json!({ "masterSettings": {} }));
+    let v = read_config().unwrap();
+    assert_eq!(v["callHistoryStorage"], json!([]));
+    assert_eq!(v["masterData"], json!([]));
+  }
+
+  #[test]
+  fn invalid_json_leaves_the_file_untouched() {
+    let _config = test_support::use_config(valid());
+    let before = fs::read_to_string(config_path()).unwrap();
+    for text in ["{\"callHistoryStorage\": [", "[]", "{\"masterData\": []}"] {
+      let err = write_public_config(text.to_string(), None).unwrap_err();
+      assert!(matches!(err.kind, ConfigWriteErrorKind::InvalidJson | ConfigWriteErrorKind::MissingKeys), "{}", text);
+      assert_eq!(fs::read_to_string(config_path()).unwrap(), before, "{}", text);
+      assert_eq!(read_config().unwrap(), valid());
+    }
+    assert!(!Path::new(&format!("{}.tmp", config_path())).exists());
+  }
+
+  #[test]
+  fn valid_payload_is_written_to_disk() {
+    let _config = test_support::use_config(valid());
+    let mut next = valid();
+    next["masterSettings"]["masterType"] = json!("AIPHONE");
+    write_public_config(next.to_string(), None).unwrap();
+    let on_disk: Value = serde_json::from_str(&fs::read_to_string(config_path()).unwrap()).unwrap();
+    assert_eq!(on_disk, next);
+  }
+
+  #[test]
+  fn duplicate_codes_are_reported_once() {
+    let v = json!({ "masterData": [
+      { "charCode": "101", "roomName": "Mawar", "bedName": "1" },
+      { "charCode": "102", "roomName": "Mawar", "bedName": "2" },
+      { "charCode": " 101 ", "roomName": "Melati", "bedName": "1" },
+      { "charCode": "101", "roomName": "Anggrek", "bedName": "1" }
+    ] });
+    let report = check_master_data(&v);
+    assert!(!report.is_valid());
+    assert_eq!(report.duplicates, vec!["101".to_string()]);
+    assert!(report.missing.is_empty());
+  }
+
+  #[test]
+  fn entries_missing_fields_are_reported() {
+    let v = json!({ "masterData": [
+      { "charCode": "101", "roomName": "Mawar", "bedName": "1" },
+      { "charCode": "102", "roomName": " " },
+      { "roomName": "Melati", "bedName": "3" }
+    ] });
+    let report = check_master_data(&v);
+    assert!(!report.is_valid());
+    assert!(report.duplicates.is_empty());
+    let missing: Vec<(usize, &str, Vec<&str>)> = report.missing.iter().map(|m| (m.index, m.char_code.as_str(), m.fields.clone())).collect();
+    assert_eq!(missing, vec![(1, "102", vec!["roomName", "bedName"]), (2, "", vec!["charCode"])]);
+  }
+
+  #[test]
+  fn clean_master_data_is_valid() {
+    let report = check_master_data(&json!({ "masterData": [{ "charCode": "101", "roomName": "Mawar", "bedName": "1" }] }));
+    assert!(report.is_valid());
+  }
+
+  #[test]
+  fn compact_history_round_trips() {
+    let v = json!({
+      "callHistoryStorage": [{ "id": 1, "code": "101", "notes": ["a,b", "c\n"] }, { "id": 2, "code": "102", "status": "active" }],
+      "masterData": [{ "charCode": "101", "roomName": "Mawar", "bedName": "1" }],
+      "masterSettings": { "compactHistory": true, "codeAliases": { "115": "105" } }
+    });
+    let text = serialize_config(&v).unwrap();
+    // one record per line
+    assert!(text.contains("\n    {\"code\":\"102\",\"id\":2,\"status\":\"active\"}\n"));
+    assert_eq!(serde_json::from_str::<Value>(&text).unwrap(), v);
+    let empty = json!({ "callHistoryStorage": [], "masterSettings": { "compactHistory": true } });
+    assert_eq!(serde_json::from_str::<Value>(&serialize_config(&empty).unwrap()).unwrap(), empty);
+  }
+
+  fn scratch(name: &str) -> String {
+    std::env::temp_dir().join(format!("ncrs-{}-{}.json", name, std::process::id())).to_string_lossy().into_owned()
+  }
+
+  #[test]
+  fn atomic_write_replaces_the_file_and_leaves_no_tmp() {
+    let path = scratch("atomic-ok");
+    fs::write(&path, "{\"old\": true}").unwrap();
+    write_atomic(&path, "{\"new\": true}").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"new\": true}");
+    assert!(!Path::new(&format!("{}.tmp", path)).exists());
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn failed_atomic_write_keeps_the_original() {
+    let path = scratch("atomic-fail");
+    fs::write(&path, "{\"old\": true}").unwrap();
+    // a directory squatting on the tmp name makes the write fail before the rename
+    let tmp = format!("{}.tmp", path);
+    fs::create_dir_all(&tmp).unwrap();
+    assert!(write_atomic(&path, "{\"new\": true}").is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"old\": true}");
+    let _ = fs::remove_dir(&tmp);
+    let _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn config_target_must_be_a_json_config() {
+    let dir = std::env::temp_dir();
+    let json = dir.join(format!("ncrs-target-{}.json", std::process::id()));
+    let fresh = dir.join(format!("ncrs-target-new-{}.json", std::process::id()));
+    fs::write(&json, "{\"callHistoryStorage\": []}").unwrap();
+    assert!(check_config_target(&json.to_string_lossy()).is_ok());
+    // a file that doesn't exist yet is created later
+    assert!(check_config_target(&fresh.to_string_lossy()).is_ok());
+    assert!(check_config_target("").is_err());
+    assert!(check_config_target("config.json").is_err());
+    assert!(check_config_target(&dir.join("ncrs-target.txt").to_string_lossy()).is_err());
+    assert!(check_config_target(&dir.join("ncrs-no-such-dir").join("config.json").to_string_lossy()).is_err());
+    fs::write(&json, "[1, 2]").unwrap();
+    assert!(check_config_target(&json.to_string_lossy()).is_err());
+    let _ = fs::remove_file(&json);
+  }
+}
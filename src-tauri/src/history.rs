@@ -0,0 +1,1087 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::{BTreeMap, HashMap}, fs, path::Path, sync::{atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+  config::{config_path, lock_config, read_config, write_atomic, write_config},
+  serial::{complete_all, emit_enclose_responses, find_master, is_muted, is_open, master_files, now_iso, now_local_compact, record_priority, record_status},
+  utils::{emit_event, EventScope, EventSink},
+};
+
+// guards resync_display against overlapping runs from several displays booting at once
+static RESYNC_RUNNING: AtomicBool = AtomicBool::new(false);
+// pause between replayed events so a large active set doesn't arrive as one burst
+const RESYNC_EMIT_GAP_MS: u64 = 50;
+const RESYNC_RECENT_LIMIT: usize = 20;
+const DEFAULT_REPEAT_INTERVAL_SECS: u64 = 30;
+const MAX_NOTE_CHARS: usize = 500;
+const MAX_SNOOZE_SECS: u64 = 60 * 60;
+const DEFAULT_ACTIVE_TICK_SECS: u64 = 5;
+const DEFAULT_ESCALATION_SECS: u64 = 120;
+const DEFAULT_ESCALATION_SCAN_SECS: u64 = 10;
+
+// id of the longest-waiting active call last announced via serial-oldest-changed
+static OLDEST_ACTIVE: Lazy<Mutex<Option<i64>>> = Lazy::new(|| Mutex::new(None));
+// record id -> epoch ms of the last repeat alert
+static LAST_REPEAT: Lazy<Mutex<HashMap<i64, u128>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// (epoch ms of the last active-calls-tick, whether calls were active then)
+static ACTIVE_TICK: Lazy<Mutex<(u128, bool)>> = Lazy::new(|| Mutex::new((0, false)));
+// epoch ms when the current snooze expires
+static SNOOZE_UNTIL: Lazy<Mutex<Option<u128>>> = Lazy::new(|| Mutex::new(None));
+// epoch ms of the last escalation scan
+static LAST_ESCALATION_SCAN: Lazy<Mutex<u128>> = Lazy::new(|| Mutex::new(0));
+
+fn is_completed(rec: &Value) -> bool { !is_open(rec) }
+
+fn str_field(rec: &Value, key: &str) -> String {
+  rec.get(key).and_then(|s| s.as_str()).unwrap_or("").to_string()
+}
+
+fn is_today(iso: &str) -> bool {
+  DateTime::parse_from_rfc3339(iso)
+    .map(|t| t.with_timezone(&Local).date_naive() == Local::now().date_naive())
+    .unwrap_or(false)
+}
+
+/// Re-emits the current call state so a display that connected mid-shift can repaint:
+/// one `nurse-call` per active record, then a `display-resync` summary with counts and
+/// today's most recent completions. Returns the number of active calls replayed.
+#[tauri::command]
+pub async fn resync_display(app: AppHandle) -> Result<usize, String> {
+  if RESYNC_RUNNING.swap(true, Ordering::SeqCst) { return Err("resync already in progress".into()); }
+  let result = replay_state(&app).await;
+  RESYNC_RUNNING.store(false, Ordering::SeqCst);
+  result
+}
+
+async fn replay_state(app: &AppHandle) -> Result<usize, String> {
+  let v = read_config()?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let active: Vec<&Value> = arr.iter().filter(|rec| !is_completed(rec)).collect();
+  for rec in &active {
+    let code = str_field(rec, "code");
+    let files = find_master(&v, &code).map(master_files).unwrap_or_default();
+    emit_event(app, EventScope::Call, "nurse-call", &json!({
+      "code": code,
+      "room": str_field(rec, "room"),
+      "bed": str_field(rec, "bed"),
+      "display": str_field(rec, "display"),
+      "files": files,
+      "resync": true,
+    }));
+    tokio::time::sleep(Duration::from_millis(RESYNC_EMIT_GAP_MS)).await;
+  }
+  let mut completed_today: Vec<&Value> = arr.iter()
+    .filter(|rec| is_completed(rec) && is_today(rec.get("resetTime").and_then(|s| s.as_str()).unwrap_or("")))
+    .collect();
+  completed_today.sort_by_key(|rec| std::cmp::Reverse(str_field(rec, "resetTime")));
+  let recent: Vec<&Value> = completed_today.iter().take(RESYNC_RECENT_LIMIT).cloned().collect();
+  emit_event(app, EventScope::Call, "display-resync", &json!({
+    "active": active.len(),
+    "completedToday": completed_today.len(),
+    "recentCompleted": recent,
+  }));
+  Ok(active.len())
+}
+
+/// Open calls shaped like their `nurse-call` payload (plus id, status and timestampMs), oldest
+/// first, for a window that mounts or reloads and may have missed the live events.
+#[tauri::command]
+pub fn nurse_call_sync() -> Result<Vec<Value>, String> {
+  let v = read_config()?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  Ok(arr.iter().filter(|rec| is_open(rec)).map(|rec| {
+    let code = str_field(rec, "code");
+    json!({
+      "id": rec.get("id").cloned().unwrap_or(Value::Null),
+      "code": code,
+      "room": str_field(rec, "room"),
+      "bed": str_field(rec, "bed"),
+      "display": str_field(rec, "display"),
+      "files": find_master(&v, &code).map(master_files).unwrap_or_default(),
+      "priority": record_priority(&v, rec),
+      "status": record_status(rec),
+      "muted": rec.get("muted").and_then(|b| b.as_bool()).unwrap_or(false),
+      "timestampMs": rec.get("timestampMs").cloned().unwrap_or(Value::Null),
+    })
+  }).collect())
+}
+
+/// Called from `long_running_thread`: re-alerts active calls whose masterData entry has
+/// `repeat: true` every `repeatIntervalSeconds` until they are acknowledged or completed.
+pub(crate) fn repeat_active_calls(app: &impl EventSink, v: &Value) {
+  // snooze_ended re-alarms everything still active once the snooze is over
+  if is_snoozed() { return; }
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  let mut last = LAST_REPEAT.lock().unwrap_or_else(|e| e.into_inner());
+  let (due, pending) = due_repeats(v, &last, now);
+  // drop everything that is no longer repeating so stale ids never fire again
+  *last = pending;
+  for payload in due { emit_event(app, EventScope::Call, "nurse-call-repeat", &payload); }
+}
+
+// The `nurse-call-repeat` payloads due at `now`, given when each call last alerted (a call's
+// id is its start in epoch ms), and the last-alert times of every call still repeating.
+fn due_repeats(v: &Value, last: &HashMap<i64, u128>, now: u128) -> (Vec<Value>, HashMap<i64, u128>) {
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let mut due = Vec::new();
+  let mut pending: HashMap<i64, u128> = HashMap::new();
+  for rec in arr {
+    // only plain "active" repeats; acknowledging or completing stops it on the next tick
+    if rec.get("status").and_then(|s| s.as_str()) != Some("active") { continue; }
+    if is_muted(v, &str_field(rec, "code"), &str_field(rec, "room")) { continue; }
+    let Some(id) = rec.get("id").and_then(|n| n.as_i64()) else { continue };
+    let code = str_field(rec, "code");
+    let Some(master) = find_master(v, &code) else { continue };
+    if !master.get("repeat").and_then(|b| b.as_bool()).unwrap_or(false) { continue; }
+    let interval = master.get("repeatIntervalSeconds").and_then(|n| n.as_u64()).unwrap_or(DEFAULT_REPEAT_INTERVAL_SECS).max(1);
+    let since = last.get(&id).copied().unwrap_or(id.max(0) as u128);
+    if now.saturating_sub(since) >= interval as u128 * 1000 {
+      due.push(json!({
+        "id": id,
+        "code": code,
+        "room": str_field(rec, "room"),
+        "bed": str_field(rec, "bed"),
+        "display": str_field(rec, "display"),
+        "files": master_files(master),
+      }));
+      pending.insert(id, now);
+    } else {
+      pending.insert(id, since);
+    }
+  }
+  (due, pending)
+}
+
+pub(crate) fn snooze_remaining_ms() -> Option<u128> {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  SNOOZE_UNTIL.lock().unwrap_or_else(|e| e.into_inner()).filter(|until| *until > now).map(|until| until - now)
+}
+
+pub(crate) fn is_snoozed() -> bool { snooze_remaining_ms().is_some() }
+
+/// Silences alarms for `seconds` (max one hour): calls are still recorded and emitted, but
+/// `nurse-call` carries `snoozed: true` so the frontend skips sound and notifications.
+/// Calling it again while snoozed restarts the timer. Returns the expiry in epoch ms.
+#[tauri::command]
+pub fn snooze(app: AppHandle, seconds: u64) -> Result<u128, String> {
+  if seconds == 0 { return Err("seconds must be greater than zero".into()); }
+  let until = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() + seconds.min(MAX_SNOOZE_SECS) as u128 * 1000;
+  *SNOOZE_UNTIL.lock().unwrap_or_else(|e| e.into_inner()) = Some(until);
+  emit_event(&app, EventScope::Call, "snooze-started", &json!({ "until": until as u64 }));
+  Ok(until)
+}
+
+/// Ends a snooze early. Returns false if none was running.
+#[tauri::command]
+pub fn cancel_snooze(app: AppHandle) -> Result<bool, String> {
+  if SNOOZE_UNTIL.lock().unwrap_or_else(|e| e.into_inner()).take().is_none() { return Ok(false); }
+  snooze_ended(&app, &read_config()?);
+  Ok(true)
+}
+
+// Called from the long-running tick: ends an expired snooze.
+pub(crate) fn expire_snooze(app: &AppHandle, v: &Value) {
+  let expired = {
+    let mut until = SNOOZE_UNTIL.lock().unwrap_or_else(|e| e.into_inner());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    if until.map(|t| t <= now).unwrap_or(false) { until.take(); true } else { false }
+  };
+  if expired { snooze_ended(app, v); }
+}
+
+// Re-alarms every call still active so nothing that arrived during the snooze stays silent.
+fn snooze_ended(app: &AppHandle, v: &Value) {
+  emit_event(app, EventScope::Call, "snooze-ended", &());
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  for rec in arr.iter().filter(|rec| rec.get("status").and_then(|s| s.as_str()) == Some("active")) {
+    let code = str_field(rec, "code");
+    if is_muted(v, &code, &str_field(rec, "room")) { continue; }
+    emit_event(app, EventScope::Call, "nurse-call", &json!({
+      "code": code,
+      "room": str_field(rec, "room"),
+      "bed": str_field(rec, "bed"),
+      "display": str_field(rec, "display"),
+      "files": find_master(v, &code).map(master_files).unwrap_or_default(),
+      "realarm": true,
+    }));
+  }
+}
+
+/// Training: re-emits the record `id` as a fresh `nurse-call` marked `training: true`, to the
+/// window that asked only. It bypasses emit_event, so it is never mirrored to LAN displays or
+/// queued for reloading windows; history is not touched and the frontend never records or
+/// forwards training calls.
+#[tauri::command]
+pub fn replay_call(window: tauri::WebviewWindow, id: i64) -> Result<(), String> {
+  let v = read_config()?;
+  let rec = v.get("callHistoryStorage")
+    .and_then(|a| a.as_array())
+    .and_then(|arr| arr.iter().find(|rec| rec.get("id").and_then(|n| n.as_i64()) == Some(id)))
+    .ok_or_else(|| format!("no call with id {}", id))?;
+  let code = str_field(rec, "code");
+  window.app_handle().send(Some(window.label()), "nurse-call", json!({
+    "code": code,
+    "room": str_field(rec, "room"),
+    "bed": str_field(rec, "bed"),
+    "display": str_field(rec, "display"),
+    "files": find_master(&v, &code).map(master_files).unwrap_or_default(),
+    "training": true,
+  }));
+  Ok(())
+}
+
+fn parse_ts(iso: &str) -> Option<DateTime<chrono::FixedOffset>> { DateTime::parse_from_rfc3339(iso).ok() }
+
+// A from/to bound: RFC3339, or a bare date (2026-10-01) meaning the start (or, for `to`, the
+// last millisecond) of that day in masterSettings.timezone, else OS local time.
+fn parse_bound(v: &Value, s: &str, end_of_day: bool) -> Option<DateTime<FixedOffset>> {
+  if let Some(ts) = parse_ts(s) { return Some(ts); }
+  let day = NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()?;
+  let at = if end_of_day { day.and_hms_milli_opt(23, 59, 59, 999)? } else { day.and_hms_opt(0, 0, 0)? };
+  let tz = v.get("masterSettings").and_then(|m| m.get("timezone")).and_then(|s| s.as_str()).and_then(|name| name.parse::<chrono_tz::Tz>().ok());
+  match tz {
+    Some(tz) => in_zone(&tz, at),
+    None => in_zone(&Local, at),
+  }
+}
+
+fn in_zone<Tz: TimeZone>(tz: &Tz, at: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+  // a time skipped by a DST change is read an hour later
+  tz.from_local_datetime(&at).earliest()
+    .or_else(|| tz.from_local_datetime(&(at + chrono::Duration::hours(1))).earliest())
+    .map(|t| t.fixed_offset())
+}
+
+type Range = (Option<DateTime<FixedOffset>>, Option<DateTime<FixedOffset>>);
+
+fn parse_range(v: &Value, from: Option<&str>, to: Option<&str>) -> Result<Range, String> {
+  let from = from.map(|f| parse_bound(v, f, false).ok_or(format!("invalid from: {}", f))).transpose()?;
+  let to = to.map(|t| parse_bound(v, t, true).ok_or(format!("invalid to: {}", t))).transpose()?;
+  Ok((from, to))
+}
+
+// tag keys/values escape commas, equals signs and spaces
+fn escape_tag(s: &str) -> String { s.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ") }
+
+const CSV_COLUMNS: [&str; 11] = ["id", "code", "room", "bed", "display", "time", "timestamp", "status", "resetTime", "resetTimeStr", "notes"];
+
+// RFC 4180: quote fields containing commas, quotes or line breaks, doubling inner quotes
+fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+/// Writes callHistoryStorage to `path` as CSV (header plus one row per record), optionally
+/// only records whose status is `status` ("active", "acknowledged" or "completed"). Returns the row count.
+#[tauri::command]
+pub fn export_call_history_csv(path: String, status: Option<String>) -> Result<usize, String> {
+  let v = read_config()?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let mut out = CSV_COLUMNS.join(",") + "\n";
+  let mut rows = 0;
+  for rec in arr {
+    if let Some(want) = status.as_deref() {
+      let have = record_status(rec);
+      if !have.eq_ignore_ascii_case(want) { continue; }
+    }
+    let fields: Vec<String> = CSV_COLUMNS.iter().map(|key| match rec.get(*key) {
+      // the same reading as the status filter above, so status-less records say "completed"
+      _ if *key == "status" => csv_field(record_status(rec)),
+      Some(Value::String(s)) => csv_field(s),
+      Some(Value::Null) | None => String::new(),
+      Some(other) => csv_field(&other.to_string()),
+    }).collect();
+    out.push_str(&fields.join(","));
+    out.push('\n');
+    rows += 1;
+  }
+  fs::write(&path, out).map_err(|e| e.to_string())?;
+  Ok(rows)
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseStats {
+  count: usize,
+  completed: usize,
+  open: usize,
+  mean_seconds: Option<f64>,
+  median_seconds: Option<f64>,
+  max_seconds: Option<f64>,
+}
+
+impl ResponseStats {
+  fn add(&mut self, response: Option<f64>, durations: &mut Vec<f64>) {
+    self.count += 1;
+    match response {
+      Some(secs) => { self.completed += 1; durations.push(secs); }
+      None => self.open += 1,
+    }
+  }
+
+  fn finish(mut self, mut durations: Vec<f64>) -> Self {
+    if durations.is_empty() { return self; }
+    durations.sort_by(|a, b| a.total_cmp(b));
+    let n = durations.len();
+    self.mean_seconds = Some(durations.iter().sum::<f64>() / n as f64);
+    self.median_seconds = Some(if n % 2 == 1 { durations[n / 2] } else { (durations[n / 2 - 1] + durations[n / 2]) / 2.0 });
+    self.max_seconds = durations.last().copied();
+    self
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeStats {
+  code: String,
+  #[serde(flatten)]
+  stats: ResponseStats,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallStats {
+  #[serde(flatten)]
+  overall: ResponseStats,
+  // records left out because a timestamp would not parse
+  skipped: usize,
+  per_code: Vec<CodeStats>,
+}
+
+/// Response times (call `timestamp` to `resetTime`) over calls placed between `from` and `to`
+/// (RFC3339 or a whole day as YYYY-MM-DD, inclusive, both optional): count, completed, open (no resetTime yet) and
+/// mean/median/max seconds, overall and per code.
+#[tauri::command]
+pub fn call_history_stats(from: Option<String>, to: Option<String>) -> Result<CallStats, String> {
+  let v = read_config()?;
+  let (from, to) = parse_range(&v, from.as_deref(), to.as_deref())?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let mut overall = (ResponseStats::default(), Vec::new());
+  let mut by_code: BTreeMap<String, (ResponseStats, Vec<f64>)> = BTreeMap::new();
+  let mut skipped = 0;
+  for rec in arr {
+    let Some(ts) = parse_ts(&str_field(rec, "timestamp")) else { skipped += 1; continue };
+    if from.map(|f| ts < f).unwrap_or(false) || to.map(|t| ts > t).unwrap_or(false) { continue; }
+    let reset = str_field(rec, "resetTime");
+    let response = if reset.is_empty() {
+      None
+    } else {
+      let Some(reset) = parse_ts(&reset) else { skipped += 1; continue };
+      Some(((reset - ts).num_milliseconds() as f64 / 1000.0).max(0.0))
+    };
+    overall.0.add(response, &mut overall.1);
+    let (stats, durations) = by_code.entry(str_field(rec, "code")).or_default();
+    stats.add(response, durations);
+  }
+  Ok(CallStats {
+    overall: overall.0.finish(overall.1),
+    skipped,
+    per_code: by_code.into_iter().map(|(code, (stats, durations))| CodeStats { code, stats: stats.finish(durations) }).collect(),
+  })
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HistoryFilter {
+  // case-insensitive substring of room, bed or display
+  text: Option<String>,
+  code: Option<String>,
+  status: Option<String>,
+  // RFC3339 or YYYY-MM-DD (a whole day), inclusive, compared with `timestamp`
+  from: Option<String>,
+  to: Option<String>,
+  limit: Option<usize>,
+  offset: Option<usize>,
+}
+
+/// callHistoryStorage records matching every field set in `filter`, highest priority first
+/// (see `resolve_priority`) and newest first within a priority, with `offset`/`limit` applied
+/// after filtering for paging.
+#[tauri::command]
+pub fn call_history_query(filter: HistoryFilter) -> Result<Vec<Value>, String> {
+  let v = read_config()?;
+  let (from, to) = parse_range(&v, filter.from.as_deref(), filter.to.as_deref())?;
+  let text = filter.text.as_deref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let matches = |rec: &&Value| {
+    if let Some(code) = filter.code.as_deref() { if str_field(rec, "code") != code { return false; } }
+    if let Some(want) = filter.status.as_deref() {
+      let have = record_status(rec);
+      if !have.eq_ignore_ascii_case(want) { return false; }
+    }
+    if let Some(text) = text.as_deref() {
+      if !["room", "bed", "display"].iter().any(|key| str_field(rec, key).to_lowercase().contains(text)) { return false; }
+    }
+    if from.is_some() || to.is_some() {
+      let Some(ts) = parse_ts(&str_field(rec, "timestamp")) else { return false };
+      if from.map(|f| ts < f).unwrap_or(false) || to.map(|t| ts > t).unwrap_or(false) { return false; }
+    }
+    true
+  };
+  // records are appended, so the newest are at the end; the sort is stable and keeps that order
+  let mut found: Vec<&Value> = arr.iter().rev().filter(matches).collect();
+  found.sort_by_key(|rec| std::cmp::Reverse(record_priority(&v, rec)));
+  Ok(found.into_iter()
+    .skip(filter.offset.unwrap_or(0))
+    .take(filter.limit.unwrap_or(usize::MAX))
+    .cloned()
+    .collect())
+}
+
+/// Writes call events between `from` and `to` (RFC3339 or a whole day as YYYY-MM-DD,
+/// inclusive) as InfluxDB line protocol: measurement `nursecall`, tags room/bed/code, fields
+/// adc/responseSeconds, UTC nanosecond timestamp of the call. Returns the number of lines written.
+#[tauri::command]
+pub fn export_influx_lineprotocol(path: String, from: Option<String>, to: Option<String>) -> Result<usize, String> {
+  let v = read_config()?;
+  let (from, to) = parse_range(&v, from.as_deref(), to.as_deref())?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let mut out = String::new();
+  let mut count = 0;
+  for rec in arr {
+    let Some(ts) = parse_ts(&str_field(rec, "timestamp")) else { continue };
+    if from.map(|f| ts < f).unwrap_or(false) || to.map(|t| ts > t).unwrap_or(false) { continue; }
+    let mut fields = Vec::new();
+    if let Some(adc) = rec.get("adc").and_then(|n| n.as_i64()) { fields.push(format!("adc={}i", adc)); }
+    if let Some(reset) = parse_ts(&str_field(rec, "resetTime")) {
+      let secs = (reset - ts).num_milliseconds() as f64 / 1000.0;
+      fields.push(format!("responseSeconds={}", secs));
+    }
+    // a line needs at least one field; older records may carry neither
+    if fields.is_empty() { continue; }
+    let Some(nanos) = ts.timestamp_nanos_opt() else { continue };
+    // influx rejects empty tag values, so unmapped calls simply omit room/bed
+    let mut line = String::from("nursecall");
+    for key in ["room", "bed", "code"] {
+      let val = str_field(rec, key);
+      if !val.is_empty() { line.push_str(&format!(",{}={}", key, escape_tag(&val))); }
+    }
+    out.push_str(&format!("{} {} {}\n", line, fields.join(","), nanos));
+    count += 1;
+  }
+  fs::write(&path, out).map_err(|e| e.to_string())?;
+  Ok(count)
+}
+
+/// Removes completed records while keeping active ones, e.g. to start a shift with a clean
+/// list. Emits `call-history-changed` and, like `call_history_delete`, `call-history-deleted`
+/// with the removed ids. Returns how many records were removed.
+#[tauri::command]
+pub fn clear_completed_calls(app: AppHandle) -> Result<usize, String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let removed = remove_completed(&mut v);
+  if !removed.is_empty() {
+    write_config(&v)?;
+    let ids: Vec<i64> = removed.iter().filter_map(|rec| rec.get("id").and_then(|n| n.as_i64())).collect();
+    emit_event(&app, EventScope::Call, "call-history-changed", &json!({ "removed": removed.len() }));
+    emit_event(&app, EventScope::Call, "call-history-deleted", &json!({ "ids": ids }));
+  }
+  Ok(removed.len())
+}
+
+// Takes every completed record out of `callHistoryStorage`, keeping open ones in order.
+fn remove_completed(v: &mut Value) -> Vec<Value> {
+  let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) else { return Vec::new() };
+  let (removed, kept): (Vec<Value>, Vec<Value>) = std::mem::take(arr).into_iter().partition(is_completed);
+  *arr = kept;
+  removed
+}
+
+/// Deletes the record `id` outright (e.g. a mistaken or test call) and emits
+/// `call-history-deleted`. A call that is still open is refused unless `force` is set.
+/// Returns whether a record was removed.
+#[tauri::command]
+pub fn call_history_delete(app: AppHandle, id: i64, force: Option<bool>) -> Result<bool, String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) else { return Ok(false) };
+  let Some(pos) = arr.iter().position(|rec| rec.get("id").and_then(|n| n.as_i64()) == Some(id)) else { return Ok(false) };
+  if !is_completed(&arr[pos]) && !force.unwrap_or(false) {
+    return Err(format!("call {} is still open; pass force to delete it", id));
+  }
+  arr.remove(pos);
+  write_config(&v)?;
+  emit_event(&app, EventScope::Call, "call-history-deleted", &json!({ "id": id }));
+  Ok(true)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryCleared {
+  cleared: usize,
+  archive_path: Option<String>,
+}
+
+// An existing day archive that can't be read or parsed is an error, never treated as empty:
+// overwriting it would lose the earlier records.
+fn read_archive(path: &str) -> Result<Vec<Value>, String> {
+  if !Path::new(path).exists() { return Ok(Vec::new()); }
+  let text = fs::read_to_string(path).map_err(|e| format!("archive {} unreadable: {}", path, e))?;
+  serde_json::from_str(&text).map_err(|e| format!("archive {} is not a JSON array ({}); move it aside and retry", path, e))
+}
+
+/// Empties callHistoryStorage, e.g. at shift change. With `archive`, the records are first
+/// appended to `history-archive/<YYYY-MM-DD>.json` next to config.json. Returns how many
+/// records were removed and emits `call-history-cleared`.
+#[tauri::command]
+pub fn call_history_clear(app: AppHandle, archive: bool) -> Result<usize, String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let records = match v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
+    Some(arr) => std::mem::take(arr),
+    None => Vec::new(),
+  };
+  let cleared = records.len();
+  let mut archive_path = None;
+  if archive && cleared > 0 {
+    let cfg_path = config_path();
+    let dir = Path::new(&cfg_path).parent().unwrap_or(Path::new(".")).join("history-archive");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}.json", Local::now().format("%Y-%m-%d"))).to_string_lossy().to_string();
+    // a second clear on the same day appends to that day's archive
+    // on error the live history is left untouched
+    let mut archived = read_archive(&path)?;
+    archived.extend(records);
+    let text = serde_json::to_string_pretty(&archived).map_err(|e| e.to_string())?;
+    write_atomic(&path, &text)?;
+    archive_path = Some(path);
+  }
+  if cleared > 0 { write_config(&v)?; }
+  emit_event(&app, EventScope::Call, "call-history-cleared", &HistoryCleared { cleared, archive_path });
+  Ok(cleared)
+}
+
+/// Appends a handover note to a call record (notes are newline-separated) and bumps
+/// `dateModified`. Notes longer than 500 characters are rejected.
+#[tauri::command]
+pub fn annotate_call(app: AppHandle, id: i64, note: String) -> Result<(), String> {
+  append_note(id, &note)?;
+  emit_event(&app, EventScope::Call, "call-history-changed", &json!({ "annotated": id }));
+  Ok(())
+}
+
+fn append_note(id: i64, note: &str) -> Result<(), String> {
+  let note = note.trim();
+  if note.is_empty() { return Err("note is empty".into()); }
+  if note.chars().count() > MAX_NOTE_CHARS { return Err(format!("note exceeds {} characters", MAX_NOTE_CHARS)); }
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let obj = v.get_mut("callHistoryStorage")
+    .and_then(|a| a.as_array_mut())
+    .and_then(|arr| arr.iter_mut().find(|rec| rec.get("id").and_then(|n| n.as_i64()) == Some(id)))
+    .and_then(|rec| rec.as_object_mut())
+    .ok_or(format!("call {} not found", id))?;
+  let notes = match obj.get("notes").and_then(|s| s.as_str()) {
+    Some(prev) if !prev.is_empty() => format!("{}\n{}", prev, note),
+    _ => note.to_string(),
+  };
+  obj.insert("notes".into(), Value::String(notes));
+  obj.insert("dateModified".into(), Value::String(now_iso()));
+  write_config(&v)
+}
+
+/// Marks call `id` as seen and being responded to: status becomes "acknowledged" with
+/// `ackTime`/`ackTimeStr`, and `nurse-call-ack` is emitted. The record stays open, so the
+/// panel reset still completes it and new triggers for its code stay de-duplicated.
+#[tauri::command]
+pub fn call_acknowledge(app: AppHandle, id: i64) -> Result<(), String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let obj = v.get_mut("callHistoryStorage")
+    .and_then(|a| a.as_array_mut())
+    .and_then(|arr| arr.iter_mut().find(|rec| rec.get("id").and_then(|n| n.as_i64()) == Some(id)))
+    .and_then(|rec| rec.as_object_mut())
+    .ok_or(format!("call {} not found", id))?;
+  // same reading as record_status: no status means an old, closed record
+  match obj.get("status").and_then(|s| s.as_str()).unwrap_or("completed") {
+    "completed" => return Err(format!("call {} is already completed", id)),
+    // keep the first acknowledgement's time
+    "acknowledged" => return Ok(()),
+    _ => {}
+  }
+  let iso = now_iso();
+  obj.insert("status".into(), Value::String("acknowledged".into()));
+  obj.insert("ackTime".into(), Value::String(iso.clone()));
+  obj.insert("ackTimeStr".into(), Value::String(now_local_compact()));
+  obj.insert("dateModified".into(), Value::String(iso.clone()));
+  let payload = json!({
+    "id": id,
+    "code": obj.get("code").cloned().unwrap_or(Value::Null),
+    "display": obj.get("display").cloned().unwrap_or(Value::Null),
+    "ackTime": iso,
+  });
+  crate::call_log::append("acknowledge", &Value::Object(obj.clone()));
+  write_config(&v)?;
+  emit_event(&app, EventScope::Call, "nurse-call-ack", &payload);
+  Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallEvent {
+  kind: &'static str,
+  id: i64,
+  code: String,
+  display: String,
+  time: String,
+}
+
+/// Lifecycle transitions (added/acknowledged/completed) after `since` (RFC3339), oldest first,
+/// derived from the records' timestamps so a reconnecting integration can catch up.
+#[tauri::command]
+pub fn call_events_since(since: String) -> Result<Vec<CallEvent>, String> {
+  let since = parse_ts(&since).ok_or(format!("invalid timestamp: {}", since))?;
+  let v = read_config()?;
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let mut events: Vec<(DateTime<chrono::FixedOffset>, CallEvent)> = Vec::new();
+  for rec in arr {
+    let id = rec.get("id").and_then(|n| n.as_i64()).unwrap_or(0);
+    for (kind, key) in [("added", "timestamp"), ("acknowledged", "ackTime"), ("completed", "resetTime")] {
+      let Some(ts) = parse_ts(&str_field(rec, key)) else { continue };
+      if ts <= since { continue; }
+      events.push((ts, CallEvent { kind, id, code: str_field(rec, "code"), display: str_field(rec, "display"), time: str_field(rec, key) }));
+    }
+  }
+  events.sort_by_key(|(ts, _)| *ts);
+  Ok(events.into_iter().map(|(_, e)| e).collect())
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveCall {
+  record: Value,
+  elapsed_seconds: i64,
+}
+
+// Active calls with server-side elapsed time, longest-waiting first.
+fn active_by_elapsed(v: &Value) -> Vec<ActiveCall> {
+  let empty = Vec::new();
+  let arr = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let now = chrono::Utc::now();
+  let mut calls: Vec<ActiveCall> = arr.iter()
+    .filter(|rec| !is_completed(rec))
+    .filter_map(|rec| parse_ts(&str_field(rec, "timestamp")).map(|ts| (ts, rec)))
+    .map(|(ts, rec)| ActiveCall { record: rec.clone(), elapsed_seconds: (now - ts.with_timezone(&chrono::Utc)).num_seconds().max(0) })
+    .collect();
+  calls.sort_by_key(|c| std::cmp::Reverse(c.elapsed_seconds));
+  calls
+}
+
+fn find_oldest_active(v: &Value) -> Option<ActiveCall> { active_by_elapsed(v).into_iter().next() }
+
+/// Active calls ordered by wait time (longest first), each with `elapsedSeconds` computed here
+/// so every display agrees regardless of its own clock.
+#[tauri::command]
+pub fn active_calls_sorted() -> Result<Vec<ActiveCall>, String> {
+  Ok(active_by_elapsed(&read_config()?))
+}
+
+/// Called from `long_running_thread`: emits `active-calls-tick` with fresh elapsed values every
+/// `activeCallsTickSeconds` (default 5) while calls are active, plus one empty tick when the
+/// last one clears so timers can stop.
+pub(crate) fn tick_active_calls(app: &AppHandle, v: &Value) {
+  let interval = v.get("masterSettings").and_then(|m| m.get("activeCallsTickSeconds")).and_then(|n| n.as_u64()).unwrap_or(DEFAULT_ACTIVE_TICK_SECS).max(1);
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  let mut tick = ACTIVE_TICK.lock().unwrap_or_else(|e| e.into_inner());
+  let calls = active_by_elapsed(v);
+  if calls.is_empty() {
+    if tick.1 { emit_event(app, EventScope::Call, "active-calls-tick", &calls); }
+    *tick = (now, false);
+    return;
+  }
+  if tick.1 && now.saturating_sub(tick.0) < interval as u128 * 1000 { return; }
+  emit_event(app, EventScope::Call, "active-calls-tick", &calls);
+  *tick = (now, true);
+}
+
+// escalationSeconds / escalationScanSeconds from masterSettings
+fn read_escalation(v: &Value) -> (u64, u64) {
+  let settings = v.get("masterSettings");
+  let secs = settings.and_then(|m| m.get("escalationSeconds")).and_then(|n| n.as_u64()).unwrap_or(DEFAULT_ESCALATION_SECS).clamp(10, 86_400);
+  let scan = settings.and_then(|m| m.get("escalationScanSeconds")).and_then(|n| n.as_u64()).unwrap_or(DEFAULT_ESCALATION_SCAN_SECS).clamp(2, 600);
+  (secs, scan)
+}
+
+// One level per full escalationSeconds unanswered; Some only when above the stamped escalationLevel.
+fn due_escalation(rec: &Value, now: DateTime<chrono::Utc>, secs: u64) -> Option<u64> {
+  if is_completed(rec) { return None; }
+  let ts = parse_ts(&str_field(rec, "timestamp"))?;
+  let elapsed = (now - ts.with_timezone(&chrono::Utc)).num_seconds().max(0) as u64;
+  let level = elapsed / secs;
+  let stamped = rec.get("escalationLevel").and_then(|n| n.as_u64()).unwrap_or(0);
+  if level > stamped { Some(level) } else { None }
+}
+
+/// Called from `long_running_thread`: every `escalationScanSeconds` (default 10), emits
+/// `nurse-call-escalation` for active/acknowledged calls unanswered past another multiple of
+/// `escalationSeconds` (default 120). The level reached is stamped as `escalationLevel` so each
+/// level fires once.
+pub(crate) fn escalate_unanswered(app: &impl EventSink, v: &Value) {
+  let (secs, scan) = read_escalation(v);
+  let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  {
+    let mut last = LAST_ESCALATION_SCAN.lock().unwrap_or_else(|e| e.into_inner());
+    if now_ms.saturating_sub(*last) < scan as u128 * 1000 { return; }
+    *last = now_ms;
+  }
+  let now = chrono::Utc::now();
+  let empty = Vec::new();
+  // cheap check on the tick's snapshot before taking the lock to write
+  if !v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty).iter().any(|rec| due_escalation(rec, now, secs).is_some()) { return; }
+  let _guard = lock_config();
+  let Ok(mut v) = read_config() else { return };
+  let mut escalated: Vec<Value> = Vec::new();
+  if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
+    for rec in arr.iter_mut() {
+      let Some(level) = due_escalation(rec, now, secs) else { continue };
+      escalated.push(escalation_payload(rec, level, secs));
+      if let Some(obj) = rec.as_object_mut() { obj.insert("escalationLevel".into(), json!(level)); }
+    }
+  }
+  if escalated.is_empty() { return; }
+  if let Err(e) = write_config(&v) { log::warn!("escalation stamp failed: {}", e); return; }
+  for payload in escalated { emit_event(app, EventScope::Call, "nurse-call-escalation", &payload); }
+}
+
+fn escalation_payload(rec: &Value, level: u64, secs: u64) -> Value {
+  json!({
+    "id": rec.get("id").cloned().unwrap_or(Value::Null),
+    "code": str_field(rec, "code"),
+    "room": str_field(rec, "room"),
+    "bed": str_field(rec, "bed"),
+    "display": str_field(rec, "display"),
+    "status": str_field(rec, "status"),
+    "level": level,
+    "escalationSeconds": secs,
+    "snoozed": is_snoozed(),
+  })
+}
+
+/// Installer check of the escalation chain: builds an active call for `code` that started
+/// `escalationSeconds` ago, runs it through the same due check and payload as the scan and
+/// emits its `nurse-call-escalation` (with `test: true`). The call is never written to
+/// callHistoryStorage, so reports and stats never see it. Returns the level emitted.
+#[tauri::command]
+pub fn test_escalation(app: AppHandle, code: String) -> Result<u64, String> {
+  run_escalation_test(&app, &read_config()?, code.trim())
+}
+
+fn run_escalation_test(app: &impl EventSink, v: &Value, code: &str) -> Result<u64, String> {
+  if code.is_empty() { return Err("code is empty".into()); }
+  let (secs, _) = read_escalation(v);
+  let now = chrono::Utc::now();
+  let started = now - chrono::Duration::seconds(secs as i64);
+  let master = find_master(v, code);
+  let room = master.map(|r| str_field(r, "roomName")).unwrap_or_default();
+  let bed = master.map(|r| str_field(r, "bedName")).unwrap_or_default();
+  let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.to_string() };
+  let rec = json!({
+    "id": -now.timestamp_millis(),
+    "code": code,
+    "room": room,
+    "bed": bed,
+    "display": display,
+    "status": "active",
+    "timestamp": started.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+  });
+  let level = due_escalation(&rec, now, secs).ok_or("test call did not qualify for escalation")?;
+  let mut payload = escalation_payload(&rec, level, secs);
+  payload["test"] = Value::Bool(true);
+  emit_event(app, EventScope::Call, "nurse-call-escalation", &payload);
+  Ok(level)
+}
+
+/// The active call that has been waiting longest, or None when nothing is active.
+#[tauri::command]
+pub fn oldest_active_call() -> Result<Option<ActiveCall>, String> {
+  Ok(find_oldest_active(&read_config()?))
+}
+
+/// Called from `long_running_thread`: emits `serial-oldest-changed` when the longest-waiting
+/// call changes (payload is null once nothing is active).
+pub(crate) fn track_oldest_active(app: &AppHandle, v: &Value) {
+  let oldest = find_oldest_active(v);
+  let id = oldest.as_ref().and_then(|o| o.record.get("id")).and_then(|n| n.as_i64());
+  let mut last = OLDEST_ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+  if *last == id { return; }
+  *last = id;
+  emit_event(app, EventScope::Call, "serial-oldest-changed", &oldest);
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseDaySummary {
+  completed: u32,
+  archived: usize,
+  cleared: usize,
+  archive_path: String,
+}
+
+/// End-of-day close: completes every active call, writes today's records to `archive_path`
+/// and, when `clear_completed` is set, removes exactly those archived records from the live
+/// history (earlier days are left alone). Runs under one config lock; if the archive can't be
+/// written nothing is changed.
+#[tauri::command]
+pub fn close_day(app: AppHandle, archive_path: String, clear_completed: Option<bool>) -> Result<CloseDaySummary, String> {
+  let (responses, archived, cleared) = {
+    let _guard = lock_config();
+    let mut v = read_config()?;
+    let responses = complete_all(&mut v);
+    let arr = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()).ok_or("callHistoryStorage is not an array")?;
+    let is_archived = |rec: &Value| is_today(&str_field(rec, "timestamp"));
+    let today: Vec<&Value> = arr.iter().filter(|rec| is_archived(rec)).collect();
+    let archived = today.len();
+    let text = serde_json::to_string_pretty(&today).map_err(|e| e.to_string())?;
+    write_atomic(&archive_path, &text)?;
+    let before = arr.len();
+    // everything archived is completed by now, so this only drops what the archive holds
+    if clear_completed.unwrap_or(false) { arr.retain(|rec| !(is_completed(rec) && is_archived(rec))); }
+    let cleared = before - arr.len();
+    if !responses.is_empty() || cleared > 0 { write_config(&v)?; }
+    (responses, archived, cleared)
+  };
+  let completed = responses.len() as u32;
+  emit_enclose_responses(&app, responses);
+  if cleared > 0 { emit_event(&app, EventScope::Call, "call-history-changed", &json!({ "removed": cleared })); }
+  Ok(CloseDaySummary { completed, archived, cleared, archive_path })
+}
+
+// Fills `timestampMs`/`resetTimeMs` on records written before the epoch fields existed.
+// Runs once at startup; returns how many records were touched.
+pub(crate) fn backfill_epoch_ms() -> Result<usize, String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) else { return Ok(0) };
+  let mut touched = 0;
+  for rec in arr.iter_mut() {
+    let Some(obj) = rec.as_object_mut() else { continue };
+    let mut changed = false;
+    for (iso_key, ms_key) in [("timestamp", "timestampMs"), ("resetTime", "resetTimeMs")] {
+      if obj.contains_key(ms_key) { continue; }
+      if let Some(ts) = obj.get(iso_key).and_then(|s| s.as_str()).and_then(parse_ts) {
+        obj.insert(ms_key.into(), json!(ts.timestamp_millis()));
+        changed = true;
+      }
+    }
+    if changed { touched += 1; }
+  }
+  if touched > 0 { write_config(&v)?; }
+  Ok(touched)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::test_support::use_config;
+  use crate::utils::RecordingSink;
+
+  fn started_ago(secs: i64) -> String {
+    (chrono::Utc::now() - chrono::Duration::seconds(secs)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+  }
+
+  #[test]
+  fn escalation_is_due_once_per_level() {
+    let now = chrono::Utc::now();
+    let rec = json!({ "code": "101", "status": "active", "timestamp": started_ago(250) });
+    assert_eq!(due_escalation(&rec, now, 120), Some(2));
+    let stamped = json!({ "code": "101", "status": "active", "timestamp": started_ago(250), "escalationLevel": 2 });
+    assert_eq!(due_escalation(&stamped, now, 120), None);
+    let fresh = json!({ "code": "101", "status": "active", "timestamp": started_ago(30) });
+    assert_eq!(due_escalation(&fresh, now, 120), None);
+    let done = json!({ "code": "101", "status": "completed", "timestamp": started_ago(250) });
+    assert_eq!(due_escalation(&done, now, 120), None);
+  }
+
+  #[test]
+  fn test_escalation_emits_without_touching_history() {
+    let v = json!({
+      "masterSettings": { "escalationSeconds": 60 },
+      "masterData": [{ "charCode": "104", "roomName": "Mawar", "bedName": "4" }],
+      "callHistoryStorage": []
+    });
+    let sink = RecordingSink::default();
+    assert_eq!(run_escalation_test(&sink, &v, "104"), Ok(1));
+    let events = sink.payloads("nurse-call-escalation");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["code"], "104");
+    assert_eq!(events[0]["display"], "Mawar - 4");
+    assert_eq!(events[0]["level"], 1);
+    assert_eq!(events[0]["escalationSeconds"], 60);
+    assert_eq!(events[0]["test"], true);
+  }
+
+  #[test]
+  fn test_escalation_needs_a_code() {
+    let sink = RecordingSink::default();
+    assert!(run_escalation_test(&sink, &json!({}), "").is_err());
+    assert!(sink.payloads("nurse-call-escalation").is_empty());
+  }
+
+  // one call started at epoch ms 1_000_000 (its id), on a bed that repeats every 30 s
+  fn repeating_site(status: &str) -> Value {
+    json!({
+      "masterSettings": {},
+      "masterData": [{ "charCode": "101", "roomName": "Mawar", "bedName": "1", "repeat": true, "repeatIntervalSeconds": 30 }],
+      "callHistoryStorage": [{ "id": 1_000_000, "code": "101", "room": "Mawar", "bed": "1", "display": "Mawar - 1", "status": status }]
+    })
+  }
+
+  #[test]
+  fn repeat_fires_on_the_interval_boundary() {
+    let v = repeating_site("active");
+    let (due, last) = due_repeats(&v, &HashMap::new(), 1_029_999);
+    assert!(due.is_empty());
+    assert_eq!(last.get(&1_000_000), Some(&1_000_000));
+    let (due, last) = due_repeats(&v, &last, 1_030_000);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0]["id"], 1_000_000);
+    assert_eq!(last.get(&1_000_000), Some(&1_030_000));
+    // the next repeat is a full interval after the last one
+    assert!(due_repeats(&v, &last, 1_059_999).0.is_empty());
+    assert_eq!(due_repeats(&v, &last, 1_060_000).0.len(), 1);
+  }
+
+  #[test]
+  fn acknowledge_or_complete_stops_the_repeat() {
+    for status in ["acknowledged", "completed"] {
+      let v = repeating_site(status);
+      let last = HashMap::from([(1_000_000, 1_000_000)]);
+      let (due, pending) = due_repeats(&v, &last, 1_090_000);
+      assert!(due.is_empty(), "{}", status);
+      assert!(pending.is_empty(), "{}", status);
+    }
+  }
+
+  #[test]
+  fn beds_without_repeat_never_repeat() {
+    let mut v = repeating_site("active");
+    v["masterData"][0]["repeat"] = json!(false);
+    let (due, pending) = due_repeats(&v, &HashMap::new(), 9_000_000);
+    assert!(due.is_empty());
+    assert!(pending.is_empty());
+  }
+
+  #[test]
+  fn clearing_completed_keeps_open_calls() {
+    let mut v = json!({ "callHistoryStorage": [
+      { "id": 1, "code": "101", "status": "completed" },
+      { "id": 2, "code": "102", "status": "active" },
+      { "id": 3, "code": "103" },
+      { "id": 4, "code": "104", "status": "acknowledged" },
+      { "id": 5, "code": "105", "status": "completed" }
+    ] });
+    let removed: Vec<Value> = remove_completed(&mut v);
+    assert_eq!(removed.iter().map(|r| r["id"].as_i64().unwrap()).collect::<Vec<_>>(), vec![1, 3, 5]);
+    let kept: Vec<i64> = v["callHistoryStorage"].as_array().unwrap().iter().map(|r| r["id"].as_i64().unwrap()).collect();
+    assert_eq!(kept, vec![2, 4]);
+  }
+
+  #[test]
+  fn clearing_without_history_removes_nothing() {
+    let mut v = json!({ "masterSettings": {} });
+    assert!(remove_completed(&mut v).is_empty());
+  }
+
+  #[test]
+  fn notes_append_and_round_trip_through_csv() {
+    let _config = use_config(json!({ "masterSettings": {}, "masterData": [], "callHistoryStorage": [{ "id": 7, "code": "107", "status": "completed" }] }));
+    append_note(7, " handed to night shift ").unwrap();
+    append_note(7, "family informed").unwrap();
+    let v = read_config().unwrap();
+    let rec = &v["callHistoryStorage"][0];
+    assert_eq!(rec["notes"], "handed to night shift\nfamily informed");
+    assert!(rec["dateModified"].as_str().is_some_and(|s| parse_ts(s).is_some()));
+    let path = std::env::temp_dir().join(format!("ncrs-test-notes-{}.csv", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    assert_eq!(export_call_history_csv(path.clone(), None), Ok(1));
+    let csv = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(csv.ends_with(",\"handed to night shift\nfamily informed\"\n"), "{}", csv);
+  }
+
+  #[test]
+  fn bad_notes_are_refused() {
+    let _config = use_config(json!({ "masterSettings": {}, "masterData": [], "callHistoryStorage": [{ "id": 8, "code": "108", "status": "active" }] }));
+    assert!(append_note(8, "   ").is_err());
+    assert!(append_note(8, &"x".repeat(MAX_NOTE_CHARS + 1)).is_err());
+    assert!(append_note(9, "unknown call").is_err());
+    assert!(read_config().unwrap()["callHistoryStorage"][0].get("notes").is_none());
+  }
+
+  #[test]
+  fn backfilled_epoch_fields_agree_with_iso_fields() {
+    // a record written live, the way the serial path stamps it
+    let (live_iso, live_ms) = crate::serial::now_stamp();
+    let _config = use_config(json!({ "callHistoryStorage": [
+      { "id": 1, "code": "101", "status": "completed", "timestamp": "2026-03-01T08:00:00Z", "resetTime": "2026-03-01T08:05:30+07:00" },
+      { "id": 2, "code": "102", "status": "active", "timestamp": "2026-03-01T09:00:00Z", "timestampMs": 1772355600000i64 },
+      { "id": 3, "code": "103", "status": "active", "timestamp": live_iso, "timestampMs": live_ms }
+    ] }));
+    assert_eq!(backfill_epoch_ms().unwrap(), 1);
+    let recs = read_config().unwrap()["callHistoryStorage"].clone();
+    assert_eq!(recs[0]["timestampMs"], json!(parse_ts("2026-03-01T08:00:00Z").unwrap().timestamp_millis()));
+    assert_eq!(recs[0]["resetTimeMs"], json!(parse_ts("2026-03-01T08:05:30+07:00").unwrap().timestamp_millis()));
+    // records that already carry their epoch fields are left alone
+    assert_eq!(recs[1]["timestampMs"], json!(1772355600000i64));
+    assert!(recs[1].get("resetTimeMs").is_none());
+    // and a live stamp is exactly what the backfill would have written
+    assert_eq!(recs[2]["timestampMs"], json!(parse_ts(&live_iso).unwrap().timestamp_millis()));
+  }
+
+  #[test]
+  fn date_only_bounds_cover_the_whole_day_in_the_configured_timezone() {
+    let _config = use_config(json!({ "masterSettings": { "timezone": "Asia/Jakarta" }, "masterData": [], "callHistoryStorage": [
+      // 23:59:59 on 30 Sep, 00:00:00 and 23:59:59 on 1 Oct, 00:00:00 on 2 Oct, all WIB (+07:00)
+      { "id": 1, "code": "101", "status": "completed", "timestamp": "2026-09-30T16:59:59Z" },
+      { "id": 2, "code": "102", "status": "completed", "timestamp": "2026-09-30T17:00:00Z" },
+      { "id": 3, "code": "103", "status": "completed", "timestamp": "2026-10-01T16:59:59Z" },
+      { "id": 4, "code": "104", "status": "completed", "timestamp": "2026-10-01T17:00:00Z" }
+    ] }));
+    let day = Some("2026-10-01".to_string());
+    let found = call_history_query(HistoryFilter { from: day.clone(), to: day.clone(), ..Default::default() }).unwrap();
+    let ids: Vec<i64> = found.iter().map(|r| r["id"].as_i64().unwrap()).collect();
+    assert_eq!(ids, vec![3, 2]);
+    assert_eq!(call_history_stats(day.clone(), day).unwrap().overall.count, 2);
+    assert!(call_history_query(HistoryFilter { from: Some("2026-10-32".into()), ..Default::default() }).is_err());
+  }
+
+  #[test]
+  fn influx_export_takes_date_only_ranges() {
+    let _config = use_config(json!({ "masterSettings": { "timezone": "Asia/Jakarta" }, "masterData": [], "callHistoryStorage": [
+      { "id": 1, "code": "101", "status": "completed", "adc": 80, "timestamp": "2026-09-30T16:59:59Z" },
+      { "id": 2, "code": "102", "status": "completed", "adc": 80, "timestamp": "2026-09-30T17:00:00Z" },
+      { "id": 3, "code": "103", "status": "completed", "adc": 80, "timestamp": "2026-10-01T17:00:00Z" }
+    ] }));
+    let path = std::env::temp_dir().join(format!("ncrs-test-influx-{}.lp", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    assert_eq!(export_influx_lineprotocol(path.clone(), Some("2026-10-01".into()), Some("2026-10-01".into())), Ok(1));
+    let lines = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(lines.starts_with("nursecall,code=102 "), "{}", lines);
+  }
+
+  #[test]
+  fn csv_status_of_a_status_less_record_is_completed() {
+    let _config = use_config(json!({ "masterSettings": {}, "masterData": [], "callHistoryStorage": [{ "id": 5, "code": "105", "timestamp": "2026-03-01T08:00:00Z" }] }));
+    let path = std::env::temp_dir().join(format!("ncrs-test-status-{}.csv", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    assert_eq!(export_call_history_csv(path.clone(), Some("completed".into())), Ok(1));
+    let csv = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    let row: Vec<&str> = csv.lines().nth(1).unwrap().split(',').collect();
+    assert_eq!(row[CSV_COLUMNS.iter().position(|c| *c == "status").unwrap()], "completed");
+  }
+}
@@ -5,11 +5,12 @@
 )]
 
 use serde::Serialize;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use tauri::{
   // state is used in Linux
   self,
   Emitter,
+  Listener,
   Manager,
 };
 use tauri_plugin_store;
@@ -18,7 +19,20 @@ use tauri_plugin_window_state;
 mod tray_icon;
 mod utils;
 mod serial;
-use crate::serial::{serial_enclose_latest, serial_enclose_all};
+mod config;
+mod history;
+mod autostart;
+mod serial_log;
+mod broadcast;
+mod call_log;
+mod audio;
+use crate::serial::{serial_enclose_latest, serial_enclose_all, serial_diagnose_code, serial_set_trace, get_parse_rules, unmapped_codes_seen, clear_unmapped_seen, set_device_baud, effective_config, set_serial_auto_connect, serial_inject, validate_audio_files, call_complete, mute_code};
+use crate::history::{resync_display, export_influx_lineprotocol, clear_completed_calls, annotate_call, call_events_since, oldest_active_call, close_day, snooze, cancel_snooze, active_calls_sorted, replay_call, call_history_clear, export_call_history_csv, call_history_stats, call_acknowledge, call_history_query, call_history_delete, nurse_call_sync, test_escalation};
+use crate::autostart::{autostart_enable, autostart_disable, autostart_is_enabled};
+use crate::serial_log::serial_logging_set;
+use crate::call_log::rebuild_history_from_log;
+use crate::audio::{list_audio_outputs, set_audio_output};
+use crate::config::{config_read, update_config, config_health, set_code_enabled, config_get_path, config_set_path, validate_master_data, bench_config_write, write_public_config};
 
 use tray_icon::{create_tray_icon, tray_update_lang, TrayState};
 use utils::long_running_thread;
@@ -36,40 +50,236 @@ pub struct DbusState(Mutex<Option<dbus::blocking::SyncConnection>>);
 
 pub struct SerialState(Mutex<Option<serial::SerialWorker>>);
 
+impl SerialState {
+  // A panic while holding the lock poisons it; recover instead of bricking every serial command.
+  fn lock(&self) -> MutexGuard<'_, Option<serial::SerialWorker>> {
+    self.0.lock().unwrap_or_else(|e| {
+      log::warn!("serial state lock was poisoned; recovering");
+      self.0.clear_poison();
+      e.into_inner()
+    })
+  }
+
+  /// Stops and drops the worker, if any; a no-op when nothing is connected.
+  fn disconnect(&self) {
+    if let Some(mut w) = self.lock().take() { w.stop(); }
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SerialSummary {
+  connected: bool,
+  port: Option<String>,
+}
+
+/// Everything the settings page needs on mount, in one round-trip.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppStateBundle {
+  settings: serde_json::Value,
+  serial: SerialSummary,
+  // masterSettings.activeProfile; null when no profile has been chosen
+  active_profile: Option<String>,
+  integrations: IntegrationStatus,
+  config_path: String,
+}
+
+/// Per-integration state: "unavailable" while this build has no MQTT or webhook client.
+#[derive(Serialize)]
+struct IntegrationStatus {
+  mqtt: &'static str,
+  webhook: &'static str,
+}
+
+const BOOTSTRAP_HISTORY_PAGE: usize = 50;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Bootstrap {
+  settings: serde_json::Value,
+  active_calls: Vec<serde_json::Value>,
+  recent_history: Vec<serde_json::Value>,
+  serial: SerialSummary,
+  config_path: String,
+  app_version: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiagnosticsVersions {
+  app: String,
+  tauri: &'static str,
+  plugin_store: &'static str,
+  plugin_updater: &'static str,
+  serialport: &'static str,
+}
+
 #[tauri::command]
 fn process_file(filepath: String) -> String {
   println!("Processing file: {}", filepath);
   "Hello from Rust!".into()
 }
 
-#[tauri::command]
-fn write_public_config(text: String) -> Result<(), String> {
-  // NOTE: dev-only path; for production, switch to a writable AppData/Documents path
-  let cfg_path = "/Users/maul/github/modern-desktop-app-template/public/config.json";
-  std::fs::write(cfg_path, text).map_err(|e| e.to_string())
-}
-
 #[tauri::command]
 fn serial_list_ports() -> Vec<String> { serial::list_ports() }
 
 #[tauri::command]
-fn serial_connect(app: tauri::AppHandle, state: tauri::State<SerialState>, port: String) -> Result<(), String> {
-  let mut guard = state.0.lock().unwrap();
+fn serial_connect(app: tauri::AppHandle, state: tauri::State<SerialState>, port: String, baud: Option<u32>, params: Option<serial::SerialParams>) -> Result<(), String> {
+  let params = serial::resolve_params(&app, &port, baud, params)?;
+  let mut guard = state.lock();
   // stop existing
   if let Some(w) = guard.as_mut() { w.stop(); }
-  let worker = serial::SerialWorker::start(app, port)?;
+  let worker = serial::SerialWorker::start(app.clone(), port.clone(), params)?;
   *guard = Some(worker);
+  // reconnects reuse these when no params are passed, and startup may auto-connect to the port
+  if let Err(e) = serial::save_last_connection(&app, &port, &params) { log::warn!("could not save serial connection: {}", e); }
   Ok(())
 }
 
+/// Support aid: plays a raw log saved with `serialLogging` back through the same parser,
+/// thresholds and de-dup as a live port, so a field incident can be reproduced without the
+/// hardware. Refused while a port is open, so replayed calls never mix with real ones.
+/// `speed` scales the recorded timing (2.0 = twice as fast); 0 feeds everything at once.
+/// Returns the number of lines processed.
+#[tauri::command]
+async fn serial_replay(app: tauri::AppHandle, state: tauri::State<'_, SerialState>, path: String, speed: f32) -> Result<usize, String> {
+  if state.lock().is_some() { return Err("disconnect the serial port before replaying a log".into()); }
+  tauri::async_runtime::spawn_blocking(move || serial::replay_log(&app, &path, speed)).await.map_err(|e| e.to_string())?
+}
+
+/// Commissioning check: listens on `port` for a few seconds with the saved (or given) settings
+/// and reports bytes received, standby pulses, well-formed vs garbage frames and the line
+/// ending, with a pass/fail verdict. Refused while the app's worker holds that port.
+#[tauri::command]
+async fn serial_diagnose(app: tauri::AppHandle, state: tauri::State<'_, SerialState>, port: String, baud: Option<u32>) -> Result<serial::DiagnosticReport, String> {
+  if state.lock().as_ref().is_some_and(|w| w.port() == port) { return Err(format!("{} is in use by the serial connection; disconnect first", port)); }
+  let params = serial::resolve_params(&app, &port, baud, None)?;
+  tauri::async_runtime::spawn_blocking(move || serial::check_port(&port, params)).await.map_err(|e| e.to_string())?
+}
+
+/// Safe to call repeatedly or before any connect; a poisoned lock is recovered, not unwrapped.
 #[tauri::command]
 fn serial_disconnect(state: tauri::State<SerialState>) -> Result<(), String> {
-  let mut guard = state.0.lock().unwrap();
-  if let Some(w) = guard.as_mut() { w.stop(); }
-  *guard = None;
+  state.disconnect();
   Ok(())
 }
 
+/// Writes `data` to the connected port (e.g. an ack or test command), with a trailing "\n"
+/// when `append_newline` is set. Returns the number of bytes written.
+#[tauri::command]
+fn serial_send(state: tauri::State<SerialState>, data: String, append_newline: Option<bool>) -> Result<usize, String> {
+  let guard = state.lock();
+  let worker = guard.as_ref().ok_or("serial port is not connected")?;
+  let mut bytes = data.into_bytes();
+  if append_newline.unwrap_or(false) { bytes.push(b'\n'); }
+  worker.send(&bytes)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SerialStatus {
+  port: Option<String>,
+  connected: bool,
+  last_read_ms: Option<u64>,
+  last_standby_ms: Option<u64>,
+  // connected but standby pulses overdue (standbyTimeoutMs)
+  degraded: bool,
+  snooze_remaining_ms: Option<u64>,
+}
+
+/// Current connection state for components that mount after serial-connected already fired.
+#[tauri::command]
+fn serial_status(state: tauri::State<SerialState>) -> SerialStatus {
+  let guard = state.lock();
+  SerialStatus {
+    port: guard.as_ref().map(|w| w.port().to_string()),
+    connected: guard.as_ref().map(|w| w.is_connected()).unwrap_or(false),
+    last_read_ms: guard.as_ref().and_then(|w| w.last_read_ms()),
+    last_standby_ms: guard.as_ref().and_then(|w| w.last_standby_ms()),
+    degraded: guard.as_ref().map(|w| w.is_degraded()).unwrap_or(false),
+    snooze_remaining_ms: history::snooze_remaining_ms().map(|ms| ms as u64),
+  }
+}
+
+fn serial_summary(state: &SerialState) -> SerialSummary {
+  let guard = state.lock();
+  SerialSummary {
+    connected: guard.as_ref().map(|w| w.is_connected()).unwrap_or(false),
+    port: guard.as_ref().map(|w| w.port().to_string()),
+  }
+}
+
+/// Internal state of the serial worker thread, or None when no worker was started.
+#[tauri::command]
+fn serial_thread_info(state: tauri::State<SerialState>) -> Option<serial::SerialThreadInfo> {
+  state.lock().as_ref().map(|w| w.thread_info())
+}
+
+#[tauri::command]
+fn get_app_state(state: tauri::State<SerialState>) -> AppStateBundle {
+  let settings = config::read_config()
+    .ok()
+    .and_then(|v| v.get("masterSettings").cloned())
+    .unwrap_or(serde_json::Value::Null);
+  let active_profile = settings.get("activeProfile").and_then(|p| p.as_str()).map(String::from);
+  AppStateBundle {
+    settings,
+    serial: serial_summary(&state),
+    active_profile,
+    integrations: IntegrationStatus { mqtt: "unavailable", webhook: "unavailable" },
+    config_path: config::config_path(),
+  }
+}
+
+/// One-shot snapshot for a freshly mounted UI, replacing the separate invokes on mount.
+#[tauri::command]
+fn bootstrap(app: tauri::AppHandle, state: tauri::State<SerialState>) -> Result<Bootstrap, String> {
+  let v = config::read_config()?;
+  let empty = Vec::new();
+  let history = v.get("callHistoryStorage").and_then(|a| a.as_array()).unwrap_or(&empty);
+  let active_calls = history.iter()
+    .filter(|rec| serial::is_open(rec))
+    .cloned()
+    .collect();
+  // records are appended, so the newest are at the end
+  let recent_history = history.iter().rev().take(BOOTSTRAP_HISTORY_PAGE).cloned().collect();
+  Ok(Bootstrap {
+    settings: v.get("masterSettings").cloned().unwrap_or(serde_json::Value::Null),
+    active_calls,
+    recent_history,
+    serial: serial_summary(&state),
+    config_path: config::config_path(),
+    app_version: app.package_info().version.to_string(),
+  })
+}
+
+/// Build and dependency versions for the about panel and bug reports (taken from Cargo.lock by build.rs).
+#[tauri::command]
+fn diagnostics_versions(app: tauri::AppHandle) -> DiagnosticsVersions {
+  DiagnosticsVersions {
+    app: app.package_info().version.to_string(),
+    tauri: env!("NCRS_VERSION_TAURI"),
+    plugin_store: env!("NCRS_VERSION_TAURI_PLUGIN_STORE"),
+    plugin_updater: env!("NCRS_VERSION_TAURI_PLUGIN_UPDATER"),
+    serialport: env!("NCRS_VERSION_SERIALPORT"),
+  }
+}
+
+// Exit hook: stops the serial worker (joining its thread, so no trigger is mid-write), then
+// writes out whatever the coalesced config flush had not reached yet. Blocks until done.
+fn shutdown(app: &tauri::AppHandle) {
+  if let Some(state) = app.try_state::<SerialState>() { state.disconnect(); }
+  broadcast::stop();
+  // after the worker is gone: it takes this lock itself while handling a trigger
+  let _guard = config::lock_config();
+  if let Ok(v) = config::read_config() { serial_log::flush(&v); }
+  match config::flush_config() {
+    Ok(()) => log::info!("shutdown: config flushed"),
+    Err(e) => log::error!("shutdown: config flush failed: {}", e),
+  }
+}
+
 #[cfg(target_os = "linux")]
 fn webkit_hidpi_workaround() {
   // See: https://github.com/spacedriveapp/spacedrive/issues/1512#issuecomment-1758550164
@@ -96,41 +306,90 @@ pub fn run() {
     .plugin(tauri_plugin_notification::init())
     .plugin(tauri_plugin_shell::init())
     .plugin(tauri_plugin_fs::init())
+    .plugin(tauri_plugin_autostart::init(tauri_plugin_autostart::MacosLauncher::LaunchAgent, Some(vec![autostart::AUTOSTART_ARG])))
+    // resolved by config::init_config_path in setup
+    .manage(config::ConfigState::default())
     // custom commands
-    .invoke_handler(tauri::generate_handler![tray_update_lang, process_file, write_public_config])
-    .invoke_handler(tauri::generate_handler![serial_list_ports, serial_connect, serial_disconnect, serial_enclose_latest, serial_enclose_all])
+    // a second invoke_handler replaces the first, so every command must be listed here
+    .invoke_handler(tauri::generate_handler![
+      tray_update_lang, process_file, write_public_config,
+      serial_list_ports, serial_connect, serial_disconnect, serial_replay, serial_diagnose, serial_send, serial_status, serial_enclose_latest, serial_enclose_all, serial_diagnose_code,
+      serial_thread_info, serial_set_trace, get_parse_rules, unmapped_codes_seen, clear_unmapped_seen,
+      set_device_baud, effective_config, set_serial_auto_connect, serial_inject, validate_audio_files, call_complete, mute_code,
+      resync_display, get_app_state, bootstrap, diagnostics_versions, config_read, update_config, config_health, set_code_enabled, config_get_path, config_set_path, validate_master_data, bench_config_write,
+      export_influx_lineprotocol,
+      clear_completed_calls, annotate_call, call_events_since, oldest_active_call, close_day, snooze, cancel_snooze,
+      active_calls_sorted, replay_call, call_history_clear, export_call_history_csv, call_history_stats, call_acknowledge, call_history_query, call_history_delete, nurse_call_sync, test_escalation,
+      autostart_enable, autostart_disable, autostart_is_enabled, serial_logging_set, rebuild_history_from_log,
+      list_audio_outputs, set_audio_output
+    ])
     // allow only one instance and propagate args and cwd to existing instance
     .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
-      app
-        .emit("newInstance", SingleInstancePayload { args, cwd })
-        .unwrap();
+      if let Err(e) = app.emit("newInstance", SingleInstancePayload { args, cwd }) {
+        log::warn!("could not forward second instance args: {}", e);
+      }
     }))
     // persistent storage with filesystem
     .plugin(tauri_plugin_store::Builder::default().build())
     // save window position and size between sessions
     // if you remove this, make sure to uncomment the mainWebview?.show line in TauriProvider.tsx
     .plugin(tauri_plugin_window_state::Builder::default().build())
+    // a reloading page has no listeners; nurse calls are held until it emits frontend-ready
+    .on_page_load(|webview, payload| {
+      if payload.event() == tauri::webview::PageLoadEvent::Started { utils::frontend_unloaded(webview.label()); }
+    })
     // custom setup code
     .setup(|app| {
       let _ = create_tray_icon(app.handle());
       app.manage(Mutex::new(TrayState::NotPlaying));
-      app.manage(SerialState(Mutex::new(None)));
+      if let Err(e) = config::init_config_path(app.handle()) { log::warn!("config file could not be created: {}", e); }
+      log::info!("config path: {}", config::config_path());
+      // before the backfill, so migrated timestamps get their epoch millis too
+      match config::run_migrations() {
+        Ok(Some(from)) => log::info!("config migrated from schema v{} to v{}", from, config::SCHEMA_VERSION),
+        Ok(None) => {}
+        Err(e) => log::warn!("config migration skipped: {}", e),
+      }
+      match history::backfill_epoch_ms() {
+        Ok(n) if n > 0 => log::info!("backfilled epoch millis on {} call records", n),
+        Ok(_) => {}
+        Err(e) => log::warn!("epoch millis backfill skipped: {}", e),
+      }
+      // the tick refreshes this too, but calls in the first seconds should already be logged
+      if let Err(e) = config::read_config().and_then(|v| { call_log::refresh(&v); serial::load_unmapped_seen(&v); broadcast::start(&v) }) { log::warn!("broadcast server not started: {}", e); }
+      let ready_handle = app.handle().clone();
+      // the window names itself, since a global listener doesn't see who emitted
+      app.listen_any("frontend-ready", move |event| {
+        if let Some(label) = serde_json::from_str::<serde_json::Value>(event.payload()).ok().and_then(|v| v.get("label")?.as_str().map(str::to_string)) {
+          utils::frontend_ready(&ready_handle, &label);
+        }
+      });
+      app.listen_any("audio-outputs", |event| audio::outputs_reported(event.payload()));
+      // after the config path is settled, since the worker reads config as soon as it opens
+      app.manage(SerialState(Mutex::new(serial::auto_connect(app.handle()))));
 
       let app_handle = app.handle().clone();
       tauri::async_runtime::spawn(async move { long_running_thread(&app_handle).await });
+      tauri::async_runtime::spawn(serial::watch_ports(app.handle().clone()));
+      tauri::async_runtime::spawn(config::flush_loop());
 
       #[cfg(target_os = "linux")]
       app.manage(DbusState(Mutex::new(
         dbus::blocking::SyncConnection::new_session().ok(),
       )));
 
-      // TODO: AUTOSTART
-      // FOLLOW: https://v2.tauri.app/plugin/autostart/
+      // workstations are expected to come back by themselves after a reboot
+      autostart::reconcile(app.handle());
+      if std::env::args().any(|a| a == autostart::AUTOSTART_ARG) { log::info!("started at login"); }
 
       Ok(())
     })
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    // runs for window-close exits and tray Quit alike, console or not
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event { shutdown(app); }
+    });
 }
 
 // useful crates
@@ -138,3 +397,35 @@ pub fn run() {
 
 // TODO: optimize permissions
 // TODO: decorations false and use custom title bar
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn poison(state: &SerialState) {
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      let _guard = state.0.lock().unwrap_or_else(|e| e.into_inner());
+      panic!("poisoning the serial state on purpose");
+    }));
+    assert!(state.0.is_poisoned());
+  }
+
+  #[test]
+  fn disconnect_without_a_connection_is_a_no_op() {
+    let state = SerialState(Mutex::new(None));
+    state.disconnect();
+    state.disconnect();
+    assert!(state.lock().is_none());
+  }
+
+  #[test]
+  fn poisoned_state_is_recovered() {
+    let state = SerialState(Mutex::new(None));
+    poison(&state);
+    state.disconnect();
+    assert!(!state.0.is_poisoned());
+    let summary = serial_summary(&state);
+    assert!(!summary.connected);
+    assert_eq!(summary.port, None);
+  }
+}
@@ -1,197 +1,1762 @@
-use serialport::available_ports;
-use std::{fs, io::Read, sync::{Arc, atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
-use tauri::{AppHandle, Emitter};
-use chrono::{Local, SecondsFormat};
+use serialport::{available_ports, DataBits, Parity, SerialPort, SerialPortType, StopBits};
+use std::{collections::{HashMap, HashSet}, io::{Read, Write}, sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use chrono::{Local, SecondsFormat, SubsecRound};
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use once_cell::sync::Lazy;
 
-static LAST_EVENT: Lazy<Mutex<(String, u128)>> = Lazy::new(|| Mutex::new((String::new(), 0)));
+use crate::utils::{emit_event, EventScope, EventSink};
+
+// standby pulses needed after a trigger before the call is completed
+const STANDBY_RESET_PULSES: u32 = 5;
+// repeat window for the same nurse-call / nurse-call-response
+const TRIGGER_DEDUP_MS: u128 = 1500;
+// repeat window for the same serial-error (open retries, disabled buttons)
+const ERROR_THROTTLE_MS: u128 = 3000;
+const OPEN_BACKOFF_MIN_MS: u64 = 500;
+const OPEN_BACKOFF_MAX_MS: u64 = 30_000;
+
+// should_emit key -> epoch ms of its last emit; each key throttles independently
+static LAST_EVENT: Lazy<Mutex<HashMap<String, u128>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// keys idle this long are dropped; no throttle window is longer (errorThrottleMs caps at 300 s)
+const LAST_EVENT_TTL_MS: u128 = 300_000;
+// ports with verbose per-line tracing enabled via serial_set_trace
+static TRACED_PORTS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+// code -> (consecutive readings past threshold, epoch ms of the latest one)
+static RECENT_READINGS: Lazy<Mutex<HashMap<String, (u32, u128)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// codes that triggered without a masterData mapping -> (count, last seen ISO)
+static UNMAPPED_SEEN: Lazy<Mutex<HashMap<String, (u64, String)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// active serial_diagnose_code session, fed by the read loop
+static DIAGNOSE: Lazy<Mutex<Option<DiagnoseSession>>> = Lazy::new(|| Mutex::new(None));
+
+struct DiagnoseSession {
+  code: String,
+  threshold: i32,
+  direction: ThresholdDirection,
+  hits: Vec<DiagnoseHit>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnoseHit {
+  line: String,
+  adc: i32,
+  threshold: i32,
+  passes: bool,
+  time: String,
+}
 
 fn should_emit(key: &str, window_ms: u128) -> bool {
   let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-  let mut g = LAST_EVENT.lock().unwrap();
-  if g.0 == key && now.saturating_sub(g.1) < window_ms { return false; }
-  *g = (key.to_string(), now);
+  let mut g = LAST_EVENT.lock().unwrap_or_else(|e| e.into_inner());
+  if g.get(key).is_some_and(|t| now.saturating_sub(*t) < window_ms) { return false; }
+  g.retain(|_, t| now.saturating_sub(*t) < LAST_EVENT_TTL_MS);
+  g.insert(key.to_string(), now);
   true
 }
 
-pub fn list_ports() -> Vec<String> {
-  let mut out = Vec::new();
-  if let Ok(ports) = available_ports() {
-    for p in ports {
-      // return the raw port name for use by serial_connect
-      out.push(p.port_name.clone());
+pub fn list_ports() -> Vec<String> {
+  let mut out = Vec::new();
+  if let Ok(ports) = available_ports() {
+    for p in ports {
+      // return the raw port name for use by serial_connect
+      out.push(p.port_name.clone());
+    }
+  }
+  out
+}
+
+const DEFAULT_BAUD: u32 = 9600;
+// upper bound for configured ADC thresholds (12-bit converters)
+const MAX_ADC: i64 = 4095;
+// how often watch_ports re-scans for plugged/unplugged adapters
+const PORT_POLL_INTERVAL_MS: u64 = 2000;
+// identical frames closer together than this are one frame echoed or sent twice
+const FRAME_DEDUP_MS: u128 = 50;
+// buffered bytes without a complete frame (or line) beyond this are discarded
+const LINE_BUFFER_CAP: usize = 8 * 1024;
+// a read with no bytes returns after this long
+const READ_TIMEOUT_MS: u64 = 200;
+// quiet time after the last byte before a pending unterminated line counts as complete
+const LINE_IDLE_MS: u128 = 500;
+// backend-owned store file for serial preferences
+pub(crate) const SERIAL_STORE: &str = "serial.json";
+
+/// Background task spawned in setup: polls available ports and emits `serial-ports-added` /
+/// `serial-ports-removed` with the names that changed since the previous scan.
+pub async fn watch_ports(app: AppHandle) {
+  let mut known: HashSet<String> = list_ports().into_iter().collect();
+  loop {
+    tokio::time::sleep(Duration::from_millis(PORT_POLL_INTERVAL_MS)).await;
+    let current: HashSet<String> = list_ports().into_iter().collect();
+    if current == known { continue; }
+    let mut added: Vec<&String> = current.difference(&known).collect();
+    let mut removed: Vec<&String> = known.difference(&current).collect();
+    added.sort();
+    removed.sort();
+    if !added.is_empty() { emit_event(&app, EventScope::Status, "serial-ports-added", &added); }
+    if !removed.is_empty() { emit_event(&app, EventScope::Status, "serial-ports-removed", &removed); }
+    known = current;
+  }
+}
+
+/// Stable identifier of the USB device behind a port: its serial number, else "vid:pid".
+pub fn device_id(port_name: &str) -> Option<String> {
+  let port = available_ports().ok()?.into_iter().find(|p| p.port_name == port_name)?;
+  match port.port_type {
+    SerialPortType::UsbPort(info) => Some(info.serial_number.unwrap_or_else(|| format!("{:04x}:{:04x}", info.vid, info.pid))),
+    _ => None,
+  }
+}
+
+fn stored_device_baud(app: &AppHandle, port_name: &str) -> Option<u32> {
+  let id = device_id(port_name)?;
+  let store = app.store(SERIAL_STORE).ok()?;
+  store.get("deviceBauds")?.get(&id)?.as_u64().map(|b| b as u32)
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParityKind {
+  None,
+  Odd,
+  Even,
+}
+
+/// Line settings for opening the port; missing fields fall back to 9600-8N1.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SerialParams {
+  pub baud_rate: u32,
+  pub data_bits: u8,
+  pub parity: ParityKind,
+  pub stop_bits: u8,
+}
+
+impl Default for SerialParams {
+  fn default() -> Self { Self { baud_rate: DEFAULT_BAUD, data_bits: 8, parity: ParityKind::None, stop_bits: 1 } }
+}
+
+impl SerialParams {
+  fn builder(&self, port_name: &str) -> Result<serialport::SerialPortBuilder, String> {
+    let data_bits = match self.data_bits {
+      5 => DataBits::Five, 6 => DataBits::Six, 7 => DataBits::Seven, 8 => DataBits::Eight,
+      n => return Err(format!("unsupported data bits: {}", n)),
+    };
+    let stop_bits = match self.stop_bits {
+      1 => StopBits::One, 2 => StopBits::Two,
+      n => return Err(format!("unsupported stop bits: {}", n)),
+    };
+    let parity = match self.parity { ParityKind::None => Parity::None, ParityKind::Odd => Parity::Odd, ParityKind::Even => Parity::Even };
+    if self.baud_rate == 0 { return Err("baud rate must be greater than zero".into()); }
+    Ok(serialport::new(port_name, self.baud_rate).data_bits(data_bits).parity(parity).stop_bits(stop_bits))
+  }
+}
+
+fn stored_last_params(app: &AppHandle) -> Option<SerialParams> {
+  let store = app.store(SERIAL_STORE).ok()?;
+  serde_json::from_value(store.get("lastParams")?).ok()
+}
+
+/// Remembers the port and params of a successful serial_connect for reconnects and startup.
+pub fn save_last_connection(app: &AppHandle, port_name: &str, params: &SerialParams) -> Result<(), String> {
+  let store = app.store(SERIAL_STORE).map_err(|e| e.to_string())?;
+  store.set("lastPort", json!(port_name));
+  store.set("lastParams", json!(params));
+  store.save().map_err(|e| e.to_string())
+}
+
+/// Enables or disables reconnecting to the last used port when the app starts.
+#[tauri::command]
+pub fn set_serial_auto_connect(app: AppHandle, enabled: bool) -> Result<(), String> {
+  let store = app.store(SERIAL_STORE).map_err(|e| e.to_string())?;
+  store.set("autoConnect", json!(enabled));
+  store.save().map_err(|e| e.to_string())
+}
+
+/// Startup: with `autoConnect` on, starts a worker for the saved port if it is present, or
+/// emits `serial-port-missing` with the saved name. The worker reports the outcome through
+/// the usual serial-connected/serial-error events.
+pub fn auto_connect(app: &AppHandle) -> Option<SerialWorker> {
+  let store = app.store(SERIAL_STORE).ok()?;
+  if !store.get("autoConnect").and_then(|b| b.as_bool()).unwrap_or(false) { return None; }
+  let port = store.get("lastPort")?.as_str()?.to_string();
+  if !list_ports().contains(&port) {
+    emit_event(app, EventScope::Status, "serial-port-missing", &port);
+    return None;
+  }
+  let params = resolve_params(app, &port, None, None).ok()?;
+  match SerialWorker::start(app.clone(), port.clone(), params) {
+    Ok(w) => Some(w),
+    Err(e) => { emit_serial_error(app, ErrorKind::OpenFailed, &port, e, false); None }
+  }
+}
+
+/// Explicit `params` win; otherwise the last-used params with the baud saved for this device.
+/// An explicit `baud` overrides either. Validated so a bogus value is refused up front.
+pub fn resolve_params(app: &AppHandle, port_name: &str, baud: Option<u32>, params: Option<SerialParams>) -> Result<SerialParams, String> {
+  let p = pick_params(baud, params, || stored_last_params(app), || stored_device_baud(app, port_name));
+  p.builder(port_name)?;
+  Ok(p)
+}
+
+// Precedence: explicit baud > explicit params > device baud over the last-used params > 9600-8N1.
+fn pick_params(baud: Option<u32>, params: Option<SerialParams>, last: impl FnOnce() -> Option<SerialParams>, device_baud: impl FnOnce() -> Option<u32>) -> SerialParams {
+  let mut p = params.unwrap_or_else(|| {
+    let mut p = last().unwrap_or_default();
+    if let Some(b) = device_baud() { p.baud_rate = b; }
+    p
+  });
+  if let Some(b) = baud { p.baud_rate = b; }
+  p
+}
+
+/// Remembers the preferred baud for a device id (serial number or "vid:pid").
+#[tauri::command]
+pub fn set_device_baud(app: AppHandle, id: String, baud: u32) -> Result<(), String> {
+  if baud == 0 { return Err("baud rate must be greater than zero".into()); }
+  let store = app.store(SERIAL_STORE).map_err(|e| e.to_string())?;
+  let mut bauds = store.get("deviceBauds").filter(|v| v.is_object()).unwrap_or_else(|| json!({}));
+  bauds[id] = json!(baud);
+  store.set("deviceBauds", bauds);
+  store.save().map_err(|e| e.to_string())
+}
+
+fn is_traced(port: &str) -> bool { TRACED_PORTS.lock().unwrap_or_else(|e| e.into_inner()).contains(port) }
+
+fn trace(app: &impl EventSink, port: &str, line: &str, decision: &str) {
+  log::trace!("[{}] {:?} -> {}", port, line, decision);
+  emit_event(app, EventScope::Diagnostic, "serial-trace", &json!({ "port": port, "line": line, "decision": decision }));
+}
+
+/// Turns verbose tracing of every read/parse decision on or off for a single port.
+#[tauri::command]
+pub fn serial_set_trace(port: String, enabled: bool) {
+  let mut g = TRACED_PORTS.lock().unwrap_or_else(|e| e.into_inner());
+  if enabled { g.insert(port); } else { g.remove(&port); }
+}
+
+/// Which side of the `:` carries the code; some controllers send `85: 103` (value first).
+#[derive(Clone, Copy, PartialEq)]
+enum FieldOrder {
+  CodeFirst,
+  ValueFirst,
+}
+
+fn read_field_order(v: &Value) -> FieldOrder {
+  let order = v.get("masterSettings").and_then(|m| m.get("fieldOrder")).and_then(|s| s.as_str()).unwrap_or("code-first");
+  if order.trim().eq_ignore_ascii_case("value-first") { FieldOrder::ValueFirst } else { FieldOrder::CodeFirst }
+}
+
+// Returns (code, rest). A reset like "901:" has nothing after the colon, so it reads the
+// same in both orders. The pair is taken at the last colon and the left field is the last
+// whitespace-separated token before it, so a prefix like "12:34:56 103: 85" still yields 103.
+fn split_fields(part: &str, order: FieldOrder) -> Option<(&str, &str)> {
+  let idx = part.rfind(':')?;
+  let left = part[..idx].split_whitespace().last().unwrap_or("");
+  let right = part[idx + 1..].trim();
+  match order {
+    FieldOrder::ValueFirst if !right.is_empty() => Some((right, left)),
+    _ => Some((left, right)),
+  }
+}
+
+/// What a serial-error is about, so the UI can suggest replugging the adapter or fixing config.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+  OpenFailed,
+  ReadFailed,
+  ConfigMissing,
+  ConfigParse,
+}
+
+/// Payload of every `serial-error`; `message` is the human-readable text shown in the monitor.
+#[derive(Clone, Serialize)]
+pub struct SerialError {
+  kind: ErrorKind,
+  port: String,
+  message: String,
+  retrying: bool,
+}
+
+fn emit_serial_error(app: &impl EventSink, kind: ErrorKind, port: &str, message: String, retrying: bool) {
+  emit_event(app, EventScope::Status, "serial-error", &SerialError { kind, port: port.to_string(), message, retrying });
+}
+
+/// Why a serial-disconnected was emitted, so the UI can tell a user stop from a fault.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisconnectReason {
+  Stopped,
+  ReadError,
+  // the standby watchdog dropped a silent port to reopen it (standbyTimeoutReconnect)
+  TimeoutWatchdog,
+  PortGone,
+  Crashed,
+}
+
+#[derive(Clone, Serialize)]
+struct DisconnectedPayload<'a> { port: &'a str, reason: DisconnectReason }
+
+fn emit_disconnected(app: &AppHandle, port: &str, reason: DisconnectReason) {
+  emit_event(app, EventScope::Status, "serial-disconnected", &DisconnectedPayload { port, reason });
+}
+
+// An unplugged adapter surfaces as one of these rather than a plain I/O failure.
+fn read_error_reason(e: &std::io::Error) -> DisconnectReason {
+  use std::io::ErrorKind::*;
+  match e.kind() {
+    NotFound | BrokenPipe | NotConnected | PermissionDenied => DisconnectReason::PortGone,
+    _ => DisconnectReason::ReadError,
+  }
+}
+
+// A loop that exits without a read failure was asked to stop.
+fn exit_reason(read_failed: Option<DisconnectReason>) -> DisconnectReason { read_failed.unwrap_or(DisconnectReason::Stopped) }
+
+// A silent panel is not a crash; reopening it must not count towards safe mode.
+fn counts_as_crash(reason: DisconnectReason) -> bool { !matches!(reason, DisconnectReason::Stopped | DisconnectReason::TimeoutWatchdog) }
+
+/// Where the worker's state machine currently is, for serial_thread_info.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkerPhase {
+  Opening,
+  Reading,
+  Backoff,
+  Stopped,
+}
+
+#[derive(Clone, Copy)]
+struct WorkerProgress {
+  phase: WorkerPhase,
+  last_iteration_ms: u128,
+  buffered_bytes: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialThreadInfo {
+  port: String,
+  alive: bool,
+  phase: WorkerPhase,
+  last_iteration_ms: u128,
+  buffered_bytes: usize,
+}
+
+fn set_phase(progress: &Mutex<WorkerProgress>, phase: WorkerPhase) {
+  let mut p = progress.lock().unwrap_or_else(|e| e.into_inner());
+  p.phase = phase;
+  p.last_iteration_ms = now_ms();
+}
+
+fn set_buffered(progress: &Mutex<WorkerProgress>, bytes: usize) {
+  progress.lock().unwrap_or_else(|e| e.into_inner()).buffered_bytes = bytes;
+}
+
+/// How the byte stream is cut into frames before parsing (masterSettings.frameMode).
+#[derive(Clone, Copy, PartialEq)]
+enum FrameMode {
+  /// ASCII lines ending in CR and/or LF (the default)
+  LineText,
+  /// `start` <frame> `end`, e.g. STX/ETX; bytes outside a frame are discarded
+  Delimited { start: u8, end: u8 },
+  /// every `n` bytes is one frame
+  FixedLength { n: usize },
+}
+
+// "line-text" (or absent), {"mode": "delimited", "start": 2, "end": 3} or
+// {"mode": "fixed-length", "n": 8}; anything malformed keeps the line default.
+fn read_frame_mode(v: &Value) -> FrameMode {
+  let Some(cfg) = v.get("masterSettings").and_then(|m| m.get("frameMode")) else { return FrameMode::LineText };
+  let mode = cfg.as_str().or_else(|| cfg.get("mode").and_then(|s| s.as_str())).unwrap_or("line-text");
+  let byte = |key: &str| cfg.get(key).and_then(|n| n.as_u64()).filter(|n| *n <= 0xFF).map(|n| n as u8);
+  match mode.trim().to_ascii_lowercase().as_str() {
+    "delimited" => match (byte("start"), byte("end")) {
+      (Some(start), Some(end)) if start != end => FrameMode::Delimited { start, end },
+      _ => { log::warn!("frameMode delimited needs distinct start/end bytes; using line-text"); FrameMode::LineText }
+    },
+    "fixed-length" => match cfg.get("n").and_then(|n| n.as_u64()).filter(|n| *n > 0 && *n as usize <= LINE_BUFFER_CAP) {
+      Some(n) => FrameMode::FixedLength { n: n as usize },
+      None => { log::warn!("frameMode fixed-length needs n between 1 and {}; using line-text", LINE_BUFFER_CAP); FrameMode::LineText }
+    },
+    _ => FrameMode::LineText,
+  }
+}
+
+// Framing layer between the port and process_frames: collects bytes and hands out complete
+// frames as they arrived. Frames are never re-joined into text, so a binary frame holding a
+// CR or LF byte stays whole.
+struct Framer {
+  mode: FrameMode,
+  // bytes after the last complete frame, carried into the next read
+  pending: Vec<u8>,
+  // 0 turns frame de-dup off
+  dedup_ms: u128,
+  // frame content -> ms it was last seen, for dedup_ms
+  recent: HashMap<Vec<u8>, u128>,
+  // see flush_idle
+  idle_ms: u128,
+  // when the last bytes arrived
+  last_byte_ms: u128,
+}
+
+impl Framer {
+  fn new(mode: FrameMode, dedup_ms: u128, idle_ms: u128) -> Self {
+    Self { mode, pending: Vec::new(), dedup_ms, recent: HashMap::new(), idle_ms, last_byte_ms: 0 }
+  }
+
+  fn buffered(&self) -> usize { self.pending.len() }
+
+  // Drops a frame identical to one seen less than dedup_ms before `now` (RS-485 echo, a panel
+  // sending twice). Separate from should_emit: this stops the repeat before it is parsed, so it
+  // can't double-count standby pulses; a real repeat after the window still goes through.
+  fn dedup(&mut self, frames: Vec<Vec<u8>>, now: u128) -> Vec<Vec<u8>> {
+    if self.dedup_ms == 0 { return frames; }
+    let window = self.dedup_ms;
+    // a replay crossing midnight goes back in time; treat anything "later" as stale
+    self.recent.retain(|_, seen| *seen <= now && now - *seen < window);
+    let recent = &mut self.recent;
+    frames.into_iter().filter(|frame| recent.insert(frame.clone(), now).is_none()).collect()
+  }
+
+  // `now` is epoch ms for a live port, or the recorded time when replaying a log.
+  fn push(&mut self, port: &str, bytes: &[u8], now: u128) -> Vec<Vec<u8>> {
+    if !bytes.is_empty() { self.last_byte_ms = now; }
+    self.pending.extend_from_slice(bytes);
+    let mut out = Vec::new();
+    match self.mode {
+      FrameMode::LineText => {
+        if let Some(end) = self.pending.iter().rposition(|b| *b == b'\n' || *b == b'\r') {
+          let text: Vec<u8> = self.pending.drain(..=end).collect();
+          out.extend(text.split(|b| *b == b'\n' || *b == b'\r').filter(|l| !l.is_empty()).map(<[u8]>::to_vec));
+        }
+      }
+      FrameMode::Delimited { start, end } => {
+        loop {
+          // anything before a start byte is between frames
+          let Some(s) = self.pending.iter().position(|b| *b == start) else { self.pending.clear(); break };
+          self.pending.drain(..s);
+          let Some(e) = self.pending.iter().position(|b| *b == end) else { break };
+          let frame: Vec<u8> = self.pending.drain(..=e).collect();
+          // a second start before the end means the first frame was cut off; keep the newer one
+          let body = &frame[frame.iter().rposition(|b| *b == start).unwrap_or(0) + 1..frame.len() - 1];
+          out.push(body.to_vec());
+        }
+      }
+      FrameMode::FixedLength { n } => {
+        let whole = self.pending.len() / n * n;
+        out.extend(self.pending.drain(..whole).collect::<Vec<u8>>().chunks(n).map(<[u8]>::to_vec));
+      }
+    }
+    // no complete frame within the cap: a misbehaving device, start over
+    if self.pending.len() > LINE_BUFFER_CAP {
+      log::warn!("{}: dropping {} unframed bytes", port, self.pending.len());
+      self.pending.clear();
+    }
+    self.dedup(out, now)
+  }
+
+  // Called on every empty read. Only once nothing has arrived for idle_ms (longer than a read
+  // timeout, so one slow read can't cut a line in two) has the line gone quiet: see flush.
+  fn flush_idle(&mut self, now: u128) -> Vec<Vec<u8>> {
+    if now.saturating_sub(self.last_byte_ms) < self.idle_ms { return Vec::new(); }
+    self.flush(now)
+  }
+
+  // The line went quiet. A pending text line is complete (some controllers send no terminator);
+  // a partial fixed-length frame is dropped so the next one starts aligned; an open delimited
+  // frame keeps waiting for its end byte.
+  fn flush(&mut self, now: u128) -> Vec<Vec<u8>> {
+    if self.pending.is_empty() { return Vec::new(); }
+    match self.mode {
+      FrameMode::LineText => {
+        let line = std::mem::take(&mut self.pending);
+        self.dedup(vec![line], now)
+      }
+      FrameMode::FixedLength { .. } => { self.pending.clear(); Vec::new() }
+      FrameMode::Delimited { .. } => Vec::new(),
+    }
+  }
+}
+
+/// What a single protocol line means, independent of any call state.
+#[derive(Debug, PartialEq)]
+enum ParsedEvent {
+  /// "<code>: <adc>", a 3-digit code with a numeric reading (a reset code with a reading too)
+  Trigger { code: String, adc: i32 },
+  /// a reset code with nothing after the colon, e.g. "901:" with target "101"
+  Enclose { target: String },
+  /// the panel's standby pulse, "99:"
+  Standby,
+}
+
+// Pure line parser: no config reads, no events. Anything it returns None for is ignored.
+fn parse_frame(line: &str, mapping: &CodeMapping, order: FieldOrder) -> Option<ParsedEvent> {
+  let (code, rest) = split_fields(line, order)?;
+  if code == mapping.standby_code { return Some(ParsedEvent::Standby); }
+  if rest.is_empty() {
+    if let Some(target) = mapping.resolve_reset_target(code) { return Some(ParsedEvent::Enclose { target }); }
+  }
+  let val = rest.split_whitespace().next().unwrap_or("");
+  if code.len() == 3 && code.chars().all(|c| c.is_ascii_digit()) && val.chars().all(|c| c.is_ascii_digit()) {
+    return Some(ParsedEvent::Trigger { code: code.to_string(), adc: val.parse().unwrap_or(0) });
+  }
+  None
+}
+
+// consecutive garbage lines before serial-noise is raised
+const NOISE_LINE_THRESHOLD: u32 = 10;
+
+// Line noise shows up as non-ASCII bytes (U+FFFD after lossy decoding) or stray control bytes;
+// such a line is dropped whole rather than risk a half-valid code reaching the parser.
+fn is_garbage(line: &str) -> bool {
+  line.chars().any(|c| !c.is_ascii() || (c.is_ascii_control() && c != '\t'))
+}
+
+// Line parser state for one connection (or for serial_inject): which call is waiting for its
+// standby reset and how many standby pulses have been seen since.
+struct FrameProcessor {
+  port: String,
+  code_mapping: CodeMapping,
+  field_order: FieldOrder,
+  tuning: SerialTuning,
+  last_active_code: Option<String>,
+  awaiting_reset: bool,
+  standby_count: u32,
+  // garbage lines in a row, reset by any clean one
+  noise_run: u32,
+  // epoch ms of the last standby pulse, 0 until the first one
+  last_standby_ms: u128,
+}
+
+impl FrameProcessor {
+  fn new(port: &str, code_mapping: CodeMapping, field_order: FieldOrder, tuning: SerialTuning) -> Self {
+    Self { port: port.to_string(), code_mapping, field_order, tuning, last_active_code: None, awaiting_reset: false, standby_count: 0, noise_run: 0, last_standby_ms: 0 }
+  }
+
+  /// Runs every line of `frame` through standby/enclose/trigger handling, exactly as the read
+  /// loop does for frames from the port (serial_inject and tests feed text this way).
+  fn process_frame(&mut self, app: &impl EventSink, frame: &str) {
+    self.process_lines(app, frame.split(['\n', '\r']));
+  }
+
+  /// Frames handed out by the Framer; each one is parsed whole. CR/LF around a frame (e.g.
+  /// padding of a fixed-length frame) is not part of it.
+  fn process_frames(&mut self, app: &impl EventSink, frames: &[Vec<u8>]) {
+    let text: Vec<String> = frames.iter().map(|f| String::from_utf8_lossy(f).into_owned()).collect();
+    self.process_lines(app, text.iter().map(|t| t.trim_matches(['\n', '\r'])));
+  }
+
+  fn process_lines<'a>(&mut self, app: &impl EventSink, lines: impl IntoIterator<Item = &'a str>) {
+    let traced = is_traced(&self.port);
+    let mut saw_standby = false;
+    for part in lines {
+      if is_garbage(part) {
+        if traced { trace(app, &self.port, part, "noise"); }
+        self.noise_run = self.noise_run.saturating_add(1);
+        if self.noise_run >= NOISE_LINE_THRESHOLD && should_emit(&format!("noise:{}", self.port), self.tuning.error_throttle_ms) {
+          emit_event(app, EventScope::Status, "serial-noise", &json!({ "port": self.port, "consecutive": self.noise_run, "sample": part.escape_debug().to_string() }));
+        }
+        continue;
+      }
+      if !part.trim().is_empty() { self.noise_run = 0; }
+      match parse_frame(part, &self.code_mapping, self.field_order) {
+        Some(ParsedEvent::Standby) => {
+          if traced { trace(app, &self.port, part, "standby"); }
+          saw_standby = true;
+          // counted only while a call awaits reset
+          if self.awaiting_reset {
+            self.standby_count = self.standby_count.saturating_add(1);
+            if self.standby_count >= self.tuning.standby_reset_pulses {
+              if let Some(code) = &self.last_active_code {
+                if let Err(e) = complete_latest_for_code(code) { report_config_error(app, &e); }
+              }
+              self.awaiting_reset = false; self.standby_count = 0;
+            }
+          }
+        }
+        Some(ParsedEvent::Enclose { target }) => {
+          if traced { trace(app, &self.port, part, "enclose"); }
+          let _ = handle_enclose(app, &target, &self.tuning);
+          self.awaiting_reset = false; self.standby_count = 0;
+        }
+        Some(ParsedEvent::Trigger { code, adc }) => {
+          // a code under diagnosis is only reported to the session: no history, no nurse-call
+          if record_diagnose(app, &code, adc, part) {
+            if traced { trace(app, &self.port, part, &format!("diagnose adc={}", adc)); }
+            continue;
+          }
+          if traced { trace(app, &self.port, part, &format!("trigger adc={}", adc)); }
+          let is_reset = self.code_mapping.resolve_reset_target(&code).is_some();
+          if is_reset { self.awaiting_reset = false; self.standby_count = 0; }
+          handle_trigger(app, &code, adc, &self.tuning);
+          if !is_reset {
+            self.last_active_code = Some(code);
+            self.awaiting_reset = true; self.standby_count = 0;
+          }
+        }
+        None => if traced && !part.trim().is_empty() { trace(app, &self.port, part, "ignored"); },
+      }
+    }
+    if saw_standby {
+      self.last_standby_ms = now_ms();
+      emit_event(app, EventScope::Diagnostic, "serial-standby-ok", &());
+    }
+  }
+}
+
+// parser state for serial_inject, kept across calls so standby resets span injected frames
+static INJECTED: Lazy<Mutex<Option<FrameProcessor>>> = Lazy::new(|| Mutex::new(None));
+
+/// Debug builds only: feeds `frame` (e.g. "101: 85\n") through the same parser, thresholds and
+/// de-dup as bytes read from the port, emitting the same serial-data/nurse-call events.
+/// Works without hardware attached.
+#[tauri::command]
+pub fn serial_inject(app: AppHandle, frame: String) -> Result<(), String> {
+  if !cfg!(debug_assertions) { return Err("serial_inject is only available in debug builds".into()); }
+  let v = crate::config::read_config()?;
+  let mut injected = INJECTED.lock().unwrap_or_else(|e| e.into_inner());
+  let frames = injected.get_or_insert_with(|| FrameProcessor::new("inject", CodeMapping::default(), FieldOrder::CodeFirst, SerialTuning::default()));
+  // follow live config edits without losing the pending-reset state
+  frames.code_mapping = read_code_mapping(&v);
+  frames.field_order = read_field_order(&v);
+  frames.tuning = read_tuning(&v);
+  emit_event(&app, EventScope::Diagnostic, "serial-data", &frame);
+  frames.process_frame(&app, &frame);
+  Ok(())
+}
+
+// how long serial_diagnose listens before reporting
+const PORT_CHECK_MS: u64 = 3000;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+  Crlf,
+  Lf,
+  Cr,
+  Mixed,
+  // nothing received, or no line breaks at all
+  None,
+}
+
+/// Result of serial_diagnose. `passed` means the port opened, a standby pulse arrived and
+/// well-formed frames outnumbered garbage ones.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticReport {
+  port: String,
+  baud_rate: u32,
+  listened_ms: u64,
+  bytes_received: usize,
+  standby_seen: bool,
+  // lines parse_frame understood (triggers, resets and standby)
+  frames: usize,
+  // clean ASCII lines that aren't protocol frames
+  unrecognized: usize,
+  garbage: usize,
+  line_ending: LineEnding,
+  // set when the port could not be opened or read
+  error: Option<String>,
+  passed: bool,
+}
+
+fn detect_line_ending(bytes: &[u8]) -> LineEnding {
+  let (mut crlf, mut lf, mut cr) = (0, 0, 0);
+  let mut i = 0;
+  while i < bytes.len() {
+    match (bytes[i], bytes.get(i + 1)) {
+      (b'\r', Some(b'\n')) => { crlf += 1; i += 1; }
+      (b'\r', _) => cr += 1,
+      (b'\n', _) => lf += 1,
+      _ => {}
+    }
+    i += 1;
+  }
+  match (crlf > 0, lf > 0, cr > 0) {
+    (false, false, false) => LineEnding::None,
+    (true, false, false) => LineEnding::Crlf,
+    (false, true, false) => LineEnding::Lf,
+    (false, false, true) => LineEnding::Cr,
+    _ => LineEnding::Mixed,
+  }
+}
+
+/// serial_diagnose: opens `port_name` on its own handle, listens for PORT_CHECK_MS and classifies
+/// what arrived with the live parser settings. Emits nothing and touches no call history; the
+/// port is closed when this returns.
+pub(crate) fn check_port(port_name: &str, params: SerialParams) -> Result<DiagnosticReport, String> {
+  let builder = params.builder(port_name)?;
+  let v = crate::config::read_config().ok();
+  let mapping = v.as_ref().map(read_code_mapping).unwrap_or_default();
+  let order = v.as_ref().map(read_field_order).unwrap_or(FieldOrder::CodeFirst);
+  let started = Instant::now();
+  let mut received: Vec<u8> = Vec::new();
+  let error = match builder.timeout(Duration::from_millis(READ_TIMEOUT_MS)).open() {
+    Ok(mut port) => {
+      let mut buf = [0u8; 1024];
+      let mut error = None;
+      while started.elapsed() < Duration::from_millis(PORT_CHECK_MS) {
+        match port.read(&mut buf) {
+          Ok(n) => received.extend_from_slice(&buf[..n]),
+          Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+          Err(e) => { error = Some(format!("read failed: {}", e)); break; }
+        }
+      }
+      error
+    }
+    Err(e) => Some(format!("open failed: {}", e)),
+  };
+  let (mut standby_seen, mut frames, mut unrecognized, mut garbage) = (false, 0, 0, 0);
+  let text = String::from_utf8_lossy(&received);
+  for line in text.split(['\n', '\r']).filter(|l| !l.trim().is_empty()) {
+    if is_garbage(line) { garbage += 1; continue; }
+    match parse_frame(line, &mapping, order) {
+      Some(ParsedEvent::Standby) => { standby_seen = true; frames += 1; }
+      Some(_) => frames += 1,
+      None => unrecognized += 1,
+    }
+  }
+  Ok(DiagnosticReport {
+    port: port_name.to_string(),
+    baud_rate: params.baud_rate,
+    listened_ms: started.elapsed().as_millis() as u64,
+    bytes_received: received.len(),
+    standby_seen,
+    frames,
+    unrecognized,
+    garbage,
+    line_ending: detect_line_ending(&received),
+    passed: error.is_none() && standby_seen && frames > garbage,
+    error,
+  })
+}
+
+// longest pause between replayed chunks, so a quiet night in the log doesn't stall the replay
+const REPLAY_MAX_GAP_MS: u64 = 10_000;
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+/// serial_replay: feeds a raw log from `serial_log::flush` through a fresh Framer and
+/// FrameProcessor on port "replay", the same path as the read loop. With `speed` > 0 the
+/// recorded gaps are replayed divided by `speed`. Returns the number of non-empty lines.
+pub(crate) fn replay_log(app: &AppHandle, path: &str, speed: f32) -> Result<usize, String> {
+  let chunks = crate::serial_log::read_chunks(path)?;
+  let v = crate::config::read_config()?;
+  if REPLAYING.swap(true, Ordering::SeqCst) { return Err("a replay is already running".into()); }
+  // clears REPLAYING even if processing panics, so the next replay isn't refused forever
+  let _guard = ReplayGuard;
+  let count = {
+    let mut frames = FrameProcessor::new("replay", read_code_mapping(&v), read_field_order(&v), read_tuning(&v));
+    let tuning = read_tuning(&v);
+    let mut framer = Framer::new(read_frame_mode(&v), tuning.frame_dedup_ms, tuning.line_idle_ms);
+    let mut count = 0;
+    let mut feed = |frames: &mut FrameProcessor, lines: Vec<Vec<u8>>| {
+      count += lines.iter().filter(|l| !String::from_utf8_lossy(l).trim().is_empty()).count();
+      frames.process_frames(app, &lines);
+    };
+    let mut last_at: Option<u32> = None;
+    for (at, bytes) in chunks {
+      if let Some(prev) = last_at.filter(|_| speed.is_finite() && speed > 0.0) {
+        // times are of day; a log spanning midnight wraps around
+        let gap = (at + 86_400_000 - prev) % 86_400_000;
+        std::thread::sleep(Duration::from_millis(((gap as f32 / speed) as u64).min(REPLAY_MAX_GAP_MS)));
+      }
+      last_at = Some(at);
+      // recorded times, so de-dup and the idle flush see the original spacing at any speed
+      feed(&mut frames, framer.flush_idle(at as u128));
+      feed(&mut frames, framer.push("replay", &bytes, at as u128));
+    }
+    // the log ends like the line going quiet
+    feed(&mut frames, framer.flush(last_at.unwrap_or(0) as u128));
+    count
+  };
+  Ok(count)
+}
+
+struct ReplayGuard;
+
+impl Drop for ReplayGuard {
+  fn drop(&mut self) { REPLAYING.store(false, Ordering::SeqCst); }
+}
+
+pub struct SerialWorker {
+  app: AppHandle,
+  port: String,
+  connected: Arc<AtomicBool>,
+  // epoch ms of the last non-empty read, 0 until the first one
+  last_read_ms: Arc<AtomicU64>,
+  // epoch ms of the last standby pulse, 0 until the first one
+  last_standby_ms: Arc<AtomicU64>,
+  // set by the standby watchdog while pulses are overdue
+  degraded: Arc<AtomicBool>,
+  progress: Arc<Mutex<WorkerProgress>>,
+  // cloned handle for serial_send, so writes never wait on the reader's port.read
+  writer: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
+  stop: Arc<AtomicBool>,
+  handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl SerialWorker {
+  pub fn start(app: AppHandle, port_name: String, params: SerialParams) -> Result<Self, String> {
+    let builder = params.builder(&port_name)?;
+    let port = port_name.clone();
+    let app_w = app.clone();
+    let connected = Arc::new(AtomicBool::new(false));
+    let connected_c = connected.clone();
+    let last_read_ms = Arc::new(AtomicU64::new(0));
+    let last_read_c = last_read_ms.clone();
+    let last_standby_ms = Arc::new(AtomicU64::new(0));
+    let last_standby_c = last_standby_ms.clone();
+    let degraded = Arc::new(AtomicBool::new(false));
+    let degraded_c = degraded.clone();
+    let progress = Arc::new(Mutex::new(WorkerProgress { phase: WorkerPhase::Opening, last_iteration_ms: now_ms(), buffered_bytes: 0 }));
+    let progress_c = progress.clone();
+    let writer: Arc<Mutex<Option<Box<dyn SerialPort>>>> = Arc::new(Mutex::new(None));
+    let writer_c = writer.clone();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_c = stop.clone();
+    let handle = std::thread::spawn(move || {
+      // epoch ms of recent read-loop failures, for safe mode
+      let mut crashes: Vec<u128> = Vec::new();
+      // set while a read failure's serial-disconnected is held back by disconnectGraceMs
+      let mut lost_at: Option<(u128, DisconnectReason)> = None;
+      // failed opens since the last successful one, driving the backoff
+      let mut open_failures: u32 = 0;
+      // epoch ms the port was lost (or first failed to open) and retries since, for serial-reconnecting
+      let mut down_since: Option<u128> = None;
+      let mut attempt: u32 = 0;
+      // retry loop: keep attempting to open the port until stopped
+      'outer: loop {
+        if stop_c.load(Ordering::Relaxed) { break 'outer; }
+        set_phase(&progress_c, WorkerPhase::Opening);
+        // re-read on every (re)connect attempt so config edits apply without a rebuild
+        let tuning = crate::config::read_config().map(|v| read_tuning(&v)).unwrap_or_default();
+        if let Some(since) = down_since {
+          attempt = attempt.saturating_add(1);
+          emit_event(&app, EventScope::Status, "serial-reconnecting", &json!({ "port": port_name, "attempt": attempt, "downtimeMs": now_ms().saturating_sub(since) }));
+        }
+        match builder.clone()
+          .timeout(Duration::from_millis(READ_TIMEOUT_MS))
+          .open() {
+            Ok(mut port) => {
+              open_failures = 0;
+              *writer_c.lock().unwrap_or_else(|e| e.into_inner()) = port.try_clone().ok();
+              connected_c.store(true, Ordering::Relaxed);
+              if let Some((t, reason)) = lost_at.take() {
+                // back within the grace window: the blip is never reported
+                if outlasted_grace(t, now_ms(), read_disconnect_grace()) { emit_disconnected(&app, &port_name, reason); }
+              }
+              let downtime_ms = down_since.take().map(|t| now_ms().saturating_sub(t)).unwrap_or(0);
+              attempt = 0;
+              emit_event(&app, EventScope::Status, "serial-connected", &json!({ "port": port_name, "downtimeMs": downtime_ms }));
+              let connected_at = now_ms();
+              let cfg = crate::config::read_config().ok();
+              let field_order = cfg.as_ref().map(read_field_order).unwrap_or(FieldOrder::CodeFirst);
+              if let Some(v) = cfg.as_ref() { log::info!("{}: ADC threshold {} ({})", port_name, trigger_threshold(v), read_master_type(v).name()); }
+              let code_mapping = cfg.as_ref().map(read_code_mapping).unwrap_or_default();
+              let mut frames = FrameProcessor::new(&port_name, code_mapping, field_order, tuning);
+              let mut framer = Framer::new(cfg.as_ref().map(read_frame_mode).unwrap_or(FrameMode::LineText), tuning.frame_dedup_ms, tuning.line_idle_ms);
+              if let Some(report) = cfg.as_ref().map(crate::config::check_master_data).filter(|r| !r.is_valid()) {
+                emit_event(&app, EventScope::Status, "config-invalid", &report);
+              }
+              let mut read_failed: Option<DisconnectReason> = None;
+              let mut buf = [0u8; 1024];
+              // read loop until error or stop
+              while !stop_c.load(Ordering::Relaxed) {
+                set_phase(&progress_c, WorkerPhase::Reading);
+                let lines = match port.read(&mut buf) {
+                  Ok(n) if n > 0 => {
+                    last_read_c.store(now_ms() as u64, Ordering::Relaxed);
+                    crate::serial_log::record(&port_name, &buf[..n]);
+                    emit_event(&app, EventScope::Diagnostic, "serial-data", &String::from_utf8_lossy(&buf[..n]).to_string());
+                    framer.push(&port_name, &buf[..n], now_ms())
+                  }
+                  Ok(_) => framer.flush_idle(now_ms()),
+                  Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => framer.flush_idle(now_ms()),
+                  Err(e) => {
+                    emit_serial_error(&app, ErrorKind::ReadFailed, &port_name, e.to_string(), true);
+                    read_failed = Some(read_error_reason(&e));
+                    break;
+                  }
+                };
+                set_buffered(&progress_c, framer.buffered());
+                // watchdog: the port can stay open while the panel has gone silent
+                if let Some(timeout) = tuning.standby_timeout_ms {
+                  let since = frames.last_standby_ms.max(connected_at);
+                  let lost = now_ms().saturating_sub(since) >= timeout;
+                  if lost != degraded_c.swap(lost, Ordering::Relaxed) {
+                    emit_event(&app, EventScope::Status, if lost { "serial-standby-lost" } else { "serial-standby-restored" }, &json!({
+                      "port": port_name,
+                      "lastStandbyMs": Some(frames.last_standby_ms).filter(|ms| *ms > 0),
+                      "lastReadMs": Some(last_read_c.load(Ordering::Relaxed)).filter(|ms| *ms > 0),
+                      "timeoutMs": timeout,
+                    }));
+                  }
+                  if lost && tuning.standby_timeout_reconnect {
+                    read_failed = Some(DisconnectReason::TimeoutWatchdog);
+                    break;
+                  }
+                }
+                if lines.is_empty() { continue; }
+                frames.process_frames(&app, &lines);
+                last_standby_c.store(frames.last_standby_ms as u64, Ordering::Relaxed);
+              }
+              // leaving read loop: disconnected or stopped
+              connected_c.store(false, Ordering::Relaxed);
+              degraded_c.store(false, Ordering::Relaxed);
+              down_since = Some(now_ms());
+              *writer_c.lock().unwrap_or_else(|e| e.into_inner()) = None;
+              let reason = exit_reason(read_failed);
+              if read_failed.is_some() && read_disconnect_grace() > 0 { lost_at = Some((now_ms(), reason)); } else { emit_disconnected(&app, &port_name, reason); }
+              if counts_as_crash(reason) {
+                let (limit, window_ms) = crate::config::read_config().map(|v| read_safe_mode(&v)).unwrap_or((5, 60_000));
+                let now = now_ms();
+                // a connection that stayed up for a whole window resets the count
+                if now.saturating_sub(connected_at) >= window_ms { crashes.clear(); }
+                crashes.push(now);
+                crashes.retain(|t| now.saturating_sub(*t) <= window_ms);
+                if crashes.len() >= limit {
+                  emit_event(&app, EventScope::Status, "serial-safe-mode", &json!({ "port": port_name, "crashes": crashes.len() }));
+                  break 'outer;
+                }
+              }
+              // slight delay before retrying
+              set_phase(&progress_c, WorkerPhase::Backoff);
+              std::thread::sleep(Duration::from_millis(800));
+            }
+            Err(e) => {
+              if let Some((t, reason)) = lost_at {
+                // outage outlasted the grace window: report it now
+                if outlasted_grace(t, now_ms(), read_disconnect_grace()) {
+                  emit_disconnected(&app, &port_name, reason);
+                  lost_at = None;
+                }
+              }
+              open_failures = open_failures.saturating_add(1);
+              down_since.get_or_insert_with(now_ms);
+              if tuning.max_open_retries.is_some_and(|max| open_failures >= max) {
+                emit_serial_error(&app, ErrorKind::OpenFailed, &port_name, e.to_string(), false);
+                emit_event(&app, EventScope::Status, "serial-give-up", &json!({ "port": port_name, "attempts": open_failures, "error": e.to_string() }));
+                break 'outer;
+              }
+              // emit throttled error and retry
+              if should_emit(&format!("open_err:{}", port_name), tuning.error_throttle_ms) {
+                emit_serial_error(&app, ErrorKind::OpenFailed, &port_name, format!("{} (retrying)", e), true);
+              }
+              // exponential backoff before retrying
+              set_phase(&progress_c, WorkerPhase::Backoff);
+              sleep_unless_stopped(&stop_c, open_backoff_ms(open_failures));
+            }
+          }
+      }
+      // stopped (or safe mode) while a disconnect was still held back
+      if let Some((_, reason)) = lost_at { emit_disconnected(&app, &port_name, reason); }
+      set_phase(&progress_c, WorkerPhase::Stopped);
+    });
+    Ok(Self { app: app_w, port, connected, last_read_ms, last_standby_ms, degraded, progress, writer, stop, handle: Some(handle) })
+  }
+
+  pub fn thread_info(&self) -> SerialThreadInfo {
+    let progress = *self.progress.lock().unwrap_or_else(|e| e.into_inner());
+    SerialThreadInfo {
+      port: self.port.clone(),
+      alive: self.handle.as_ref().map(|h| !h.is_finished()).unwrap_or(false),
+      phase: progress.phase,
+      last_iteration_ms: progress.last_iteration_ms,
+      buffered_bytes: progress.buffered_bytes,
+    }
+  }
+
+  pub fn port(&self) -> &str { &self.port }
+
+  pub fn is_connected(&self) -> bool { self.connected.load(Ordering::Relaxed) }
+
+  pub fn last_read_ms(&self) -> Option<u64> {
+    Some(self.last_read_ms.load(Ordering::Relaxed)).filter(|ms| *ms > 0)
+  }
+
+  pub fn last_standby_ms(&self) -> Option<u64> {
+    Some(self.last_standby_ms.load(Ordering::Relaxed)).filter(|ms| *ms > 0)
+  }
+
+  /// Connected, but no standby pulse within `standbyTimeoutMs`.
+  pub fn is_degraded(&self) -> bool { self.degraded.load(Ordering::Relaxed) }
+
+  /// Writes `data` to the open port; errors when the worker is between connections.
+  pub fn send(&self, data: &[u8]) -> Result<usize, String> {
+    let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+    let port = writer.as_mut().ok_or_else(|| format!("{} is not open", self.port))?;
+    port.write_all(data).map_err(|e| e.to_string())?;
+    port.flush().map_err(|e| e.to_string())?;
+    Ok(data.len())
+  }
+
+  pub fn stop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+    if let Some(h) = self.handle.take() {
+      // a panicked worker never reached its own serial-disconnected
+      if h.join().is_err() { emit_disconnected(&self.app, &self.port, DisconnectReason::Crashed); }
+    }
+  }
+}
+
+fn now_ms() -> u128 { SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() }
+
+// Safe mode: after `safeModeCrashLimit` read failures within `safeModeWindowMs` the worker
+// stops retrying until serial_connect is called again.
+fn read_safe_mode(v: &Value) -> (usize, u128) {
+  let settings = v.get("masterSettings");
+  let limit = settings.and_then(|m| m.get("safeModeCrashLimit")).and_then(|n| n.as_u64()).unwrap_or(5).clamp(1, 100) as usize;
+  let window_ms = settings.and_then(|m| m.get("safeModeWindowMs")).and_then(|n| n.as_u64()).unwrap_or(60_000).clamp(1_000, 3_600_000) as u128;
+  (limit, window_ms)
+}
+
+// disconnectGraceMs: how long a lost port may take to come back before serial-disconnected is emitted
+fn disconnect_grace_ms(v: &Value) -> u128 {
+  v.get("masterSettings").and_then(|m| m.get("disconnectGraceMs")).and_then(|n| n.as_u64()).unwrap_or(0).min(60_000) as u128
+}
+
+// whether a port lost at `lost_at` has been down long enough to report serial-disconnected
+fn outlasted_grace(lost_at: u128, now: u128, grace_ms: u128) -> bool { now.saturating_sub(lost_at) >= grace_ms }
+
+#[derive(Clone, Copy)]
+struct SerialTuning {
+  standby_reset_pulses: u32,
+  trigger_dedup_ms: u128,
+  error_throttle_ms: u128,
+  // consecutive failed opens before serial-give-up; None retries forever
+  max_open_retries: Option<u32>,
+  // standby watchdog; None leaves it off for panels that never send 99:
+  standby_timeout_ms: Option<u128>,
+  // reopen the port when the watchdog trips instead of only flagging it degraded
+  standby_timeout_reconnect: bool,
+  frame_dedup_ms: u128,
+  line_idle_ms: u128,
+}
+
+impl Default for SerialTuning {
+  fn default() -> Self {
+    Self { standby_reset_pulses: STANDBY_RESET_PULSES, trigger_dedup_ms: TRIGGER_DEDUP_MS, error_throttle_ms: ERROR_THROTTLE_MS, max_open_retries: None, standby_timeout_ms: None, standby_timeout_reconnect: false, frame_dedup_ms: FRAME_DEDUP_MS, line_idle_ms: LINE_IDLE_MS }
+  }
+}
+
+// standbyResetPulses / triggerDedupMs / errorThrottleMs / maxOpenRetries, clamped so a typo can't disable resets or de-dup
+fn read_tuning(v: &Value) -> SerialTuning {
+  let settings = v.get("masterSettings");
+  let get = |key: &str| settings.and_then(|m| m.get(key)).and_then(|n| n.as_u64());
+  SerialTuning {
+    standby_reset_pulses: get("standbyResetPulses").map(|n| n.clamp(1, 50) as u32).unwrap_or(STANDBY_RESET_PULSES),
+    trigger_dedup_ms: get("triggerDedupMs").map(|n| n.clamp(100, 60_000) as u128).unwrap_or(TRIGGER_DEDUP_MS),
+    error_throttle_ms: get("errorThrottleMs").map(|n| n.clamp(500, 300_000) as u128).unwrap_or(ERROR_THROTTLE_MS),
+    max_open_retries: get("maxOpenRetries").filter(|n| *n > 0).map(|n| n.min(100_000) as u32),
+    standby_timeout_ms: get("standbyTimeoutMs").filter(|n| *n > 0).map(|n| n.clamp(1_000, 600_000) as u128),
+    standby_timeout_reconnect: settings.and_then(|m| m.get("standbyTimeoutReconnect")).and_then(|b| b.as_bool()).unwrap_or(false),
+    frame_dedup_ms: get("frameDedupMs").map(|n| n.min(5_000) as u128).unwrap_or(FRAME_DEDUP_MS),
+    // never down to a single read timeout, which is exactly the split this guards against
+    line_idle_ms: get("lineIdleMs").map(|n| n.clamp(READ_TIMEOUT_MS + 1, 10_000) as u128).unwrap_or(LINE_IDLE_MS),
+  }
+}
+
+// 500 ms after the first failed open, doubling up to 30 s
+fn open_backoff_ms(failures: u32) -> u64 {
+  OPEN_BACKOFF_MIN_MS.saturating_mul(1u64 << failures.saturating_sub(1).min(16)).min(OPEN_BACKOFF_MAX_MS)
+}
+
+// sleeps in short steps so stop() is not held up by a long backoff
+fn sleep_unless_stopped(stop: &AtomicBool, ms: u64) {
+  let until = Instant::now() + Duration::from_millis(ms);
+  while !stop.load(Ordering::Relaxed) {
+    let left = until.saturating_duration_since(Instant::now());
+    if left.is_zero() { break; }
+    std::thread::sleep(left.min(Duration::from_millis(100)));
+  }
+}
+
+fn read_disconnect_grace() -> u128 {
+  crate::config::read_config().map(|v| disconnect_grace_ms(&v)).unwrap_or(0)
+}
+
+pub(crate) fn now_iso() -> String { chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true) }
+// ISO string and epoch millis taken from the same whole-second instant, so the millis are
+// exactly what parsing the ISO string gives (and what backfill_epoch_ms writes).
+pub(crate) fn now_stamp() -> (String, i64) {
+  let at = chrono::Utc::now().trunc_subsecs(0);
+  (at.to_rfc3339_opts(SecondsFormat::Secs, true), at.timestamp_millis())
+}
+
+/// A call record's status. Records from old versions may have no string `status`; they were
+/// never closed by this version and count as "completed", so they can't block a code forever.
+pub(crate) fn record_status(rec: &Value) -> &str {
+  rec.get("status").and_then(|s| s.as_str()).unwrap_or("completed")
+}
+
+/// Still waiting for a response: active or acknowledged (see `record_status`).
+pub(crate) fn is_open(rec: &Value) -> bool { record_status(rec) != "completed" }
+
+// Completes a call record: status, reset times (ISO, local, epoch ms) and dateModified.
+fn mark_completed(obj: &mut serde_json::Map<String, Value>) {
+  let (iso, ms) = now_stamp();
+  obj.insert("status".into(), Value::String("completed".into()));
+  obj.insert("resetTime".into(), Value::String(iso.clone()));
+  obj.insert("resetTimeStr".into(), Value::String(now_local_compact()));
+  obj.insert("resetTimeMs".into(), json!(ms));
+  obj.insert("dateModified".into(), Value::String(iso));
+  crate::call_log::append("complete", &Value::Object(obj.clone()));
+}
+// display format of the `time`/`*Str` fields unless masterSettings.timeFormat overrides it
+const DEFAULT_TIME_FORMAT: &str = "%H:%M:%S.%-m-%-d-%Y";
+
+// chrono panics while formatting an invalid strftime string, so reject those up front
+fn valid_time_format(fmt: &str) -> bool {
+  !fmt.is_empty() && chrono::format::StrftimeItems::new(fmt).all(|item| !matches!(item, chrono::format::Item::Error))
+}
+
+// `at` in masterSettings.timezone (IANA name, default: OS local time) and masterSettings.timeFormat;
+// an invalid value of either falls back to the default with a warning.
+fn format_display_time(v: &Value, at: chrono::DateTime<chrono::Utc>) -> String {
+  let settings = v.get("masterSettings");
+  let fmt = match settings.and_then(|m| m.get("timeFormat")).and_then(|s| s.as_str()) {
+    Some(f) if valid_time_format(f) => f,
+    Some(f) => { log::warn!("invalid timeFormat {:?}; using the default", f); DEFAULT_TIME_FORMAT }
+    None => DEFAULT_TIME_FORMAT,
+  };
+  let tz = settings.and_then(|m| m.get("timezone")).and_then(|s| s.as_str()).filter(|s| !s.is_empty());
+  match tz.map(|name| name.parse::<chrono_tz::Tz>().map_err(|_| name)) {
+    Some(Ok(tz)) => at.with_timezone(&tz).format(fmt).to_string(),
+    Some(Err(name)) => { log::warn!("unknown timezone {:?}; using local time", name); at.with_timezone(&Local).format(fmt).to_string() }
+    None => at.with_timezone(&Local).format(fmt).to_string(),
+  }
+}
+
+// Human-readable now for the display fields; stored ISO fields stay UTC RFC3339.
+pub(crate) fn now_local_compact() -> String {
+  let v = crate::config::read_config().unwrap_or(Value::Null);
+  format_display_time(&v, chrono::Utc::now())
+}
+
+/// Panel vendor from `masterSettings.masterType`. Per-vendor protocol defaults live in its
+/// impl, so supporting another panel means adding a variant and its match arms here.
+#[derive(Clone, PartialEq)]
+enum MasterType {
+  Commax,
+  Aiphone,
+  Other(String),
+}
+
+impl MasterType {
+  fn parse(name: &str) -> Self {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("AIPHONE") { MasterType::Aiphone }
+    else if name.is_empty() || name.eq_ignore_ascii_case("Commax") { MasterType::Commax }
+    else { MasterType::Other(name.to_string()) }
+  }
+
+  fn name(&self) -> &str {
+    match self {
+      MasterType::Commax => "Commax",
+      MasterType::Aiphone => "AIPHONE",
+      MasterType::Other(name) => name,
+    }
+  }
+
+  fn default_threshold(&self) -> i32 {
+    match self {
+      MasterType::Aiphone => 150,
+      MasterType::Commax | MasterType::Other(_) => 70,
+    }
+  }
+
+  // (reset prefix, call prefix): a reset "90x" completes call "10x"
+  fn reset_mapping(&self) -> (&'static str, &'static str) {
+    match self {
+      MasterType::Commax | MasterType::Aiphone | MasterType::Other(_) => ("90", "10"),
+    }
+  }
+
+  // the panel's standby pulse, "99:"
+  fn standby_code(&self) -> &'static str {
+    match self {
+      MasterType::Commax | MasterType::Aiphone | MasterType::Other(_) => "99",
+    }
+  }
+}
+
+/// How a reset code maps to the call it completes: `resetPrefix` + `keepDigits` digits
+/// completes `triggerPrefix` + the same digits (901 -> 101 on a Commax), plus the panel's
+/// standby code. Unset fields come from the `MasterType`.
+#[derive(Clone)]
+struct CodeMapping {
+  reset_prefix: String,
+  trigger_prefix: String,
+  keep_digits: usize,
+  standby_code: String,
+}
+
+impl Default for CodeMapping {
+  fn default() -> Self { Self::for_master(&MasterType::Commax) }
+}
+
+impl CodeMapping {
+  fn for_master(master_type: &MasterType) -> Self {
+    let (reset, call) = master_type.reset_mapping();
+    Self { reset_prefix: reset.into(), trigger_prefix: call.into(), keep_digits: 1, standby_code: master_type.standby_code().into() }
+  }
+
+  /// The call code a reset code completes, or None if `code` is not a reset code.
+  fn resolve_reset_target(&self, code: &str) -> Option<String> {
+    if self.reset_prefix.is_empty() { return None; }
+    let rest = code.strip_prefix(self.reset_prefix.as_str())
+      .filter(|r| r.len() == self.keep_digits && r.chars().all(|c| c.is_ascii_digit()))?;
+    Some(format!("{}{}", self.trigger_prefix, rest))
+  }
+}
+
+// masterSettings.codeMapping { resetPrefix, triggerPrefix, keepDigits }; missing fields keep
+// the master type's defaults
+fn read_code_mapping(v: &Value) -> CodeMapping {
+  let mut mapping = CodeMapping::for_master(&read_master_type(v));
+  let Some(m) = v.get("masterSettings").and_then(|m| m.get("codeMapping")) else { return mapping };
+  let prefix = |key: &str| m.get(key).and_then(|s| s.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+  if let Some(p) = prefix("resetPrefix") { mapping.reset_prefix = p; }
+  if let Some(p) = prefix("triggerPrefix") { mapping.trigger_prefix = p; }
+  if let Some(n) = m.get("keepDigits").and_then(|n| n.as_u64()) { mapping.keep_digits = n.clamp(1, 4) as usize; }
+  mapping
+}
+
+fn read_master_type(v: &Value) -> MasterType {
+  MasterType::parse(
+    v.get("masterSettings")
+      .and_then(|m| m.get("masterType").or_else(|| m.get("master")).or_else(|| m.get("type")))
+      .and_then(|s| s.as_str())
+      .unwrap_or("Commax"),
+  )
+}
+
+pub(crate) fn find_master<'a>(v: &'a Value, code: &str) -> Option<&'a Value> {
+  v.get("masterData")
+    .and_then(|a| a.as_array())
+    .and_then(|md| md.iter().find(|r| r.get("charCode").and_then(|s| s.as_str()) == Some(code)))
+}
+
+// sound files v1..v6 of a masterData entry, skipping blanks and "-"
+pub(crate) fn master_files(r: &Value) -> Vec<String> {
+  let mut files = Vec::new();
+  for key in ["v1","v2","v3","v4","v5","v6"] {
+    if let Some(f) = r.get(key).and_then(|s| s.as_str()) {
+      if !f.is_empty() && f != "-" { files.push(f.to_string()); }
+    }
+  }
+  files
+}
+
+// masterSettings.audioDir, else sounds/ next to config.json (public/sounds in dev, served as /sounds/)
+fn audio_dir(v: &Value) -> std::path::PathBuf {
+  if let Some(dir) = v.get("masterSettings").and_then(|m| m.get("audioDir")).and_then(|s| s.as_str()).filter(|s| !s.is_empty()) {
+    return std::path::PathBuf::from(dir);
+  }
+  let cfg_path = crate::config::config_path();
+  std::path::Path::new(&cfg_path).parent().unwrap_or(std::path::Path::new(".")).join("sounds")
+}
+
+// (present, missing); without an audio directory on disk nothing can be checked, so all pass
+fn split_audio(v: &Value, files: Vec<String>) -> (Vec<String>, Vec<String>) {
+  let dir = audio_dir(v);
+  if !dir.is_dir() { return (files, Vec::new()); }
+  files.into_iter().partition(|f| dir.join(f).is_file())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingAudio {
+  code: String,
+  files: Vec<String>,
+}
+
+/// Pre-flight for installers: every masterData entry whose v1..v6 sound files are not in the
+/// audio directory (`masterSettings.audioDir`, default `sounds/` next to config.json).
+#[tauri::command]
+pub fn validate_audio_files() -> Result<Vec<MissingAudio>, String> {
+  let v = crate::config::read_config()?;
+  let dir = audio_dir(&v);
+  if !dir.is_dir() { return Err(format!("audio directory {} not found", dir.display())); }
+  let empty = Vec::new();
+  let md = v.get("masterData").and_then(|a| a.as_array()).unwrap_or(&empty);
+  Ok(md.iter().filter_map(|r| {
+    let (_, missing) = split_audio(&v, master_files(r));
+    if missing.is_empty() { return None; }
+    Some(MissingAudio { code: r.get("charCode").and_then(|s| s.as_str()).unwrap_or("").to_string(), files: missing })
+  }).collect())
+}
+
+// `codeAliases` maps extra physical codes to the charCode whose room/bed they share,
+// e.g. { "115": "105" } for a pull-cord wired next to bed 105's bedside button.
+fn resolve_alias(v: &Value, code: &str) -> String {
+  v.get("masterSettings")
+    .and_then(|m| m.get("codeAliases"))
+    .and_then(|a| a.get(code))
+    .and_then(|c| c.as_str())
+    .unwrap_or(code)
+    .to_string()
+}
+
+// With `aliasDedup` (default true) aliased codes are recorded and de-duplicated as one
+// logical call under the canonical code; otherwise each keeps its own records.
+fn read_alias_dedup(v: &Value) -> bool {
+  v.get("masterSettings").and_then(|m| m.get("aliasDedup")).and_then(|b| b.as_bool()).unwrap_or(true)
+}
+
+fn logical_code(v: &Value, code: &str) -> String {
+  if read_alias_dedup(v) { resolve_alias(v, code) } else { code.to_string() }
+}
+
+// `resetRequiresThreshold` (default true): whether a 90x reset must pass the ADC threshold too.
+fn read_reset_requires_threshold(v: &Value) -> bool {
+  v.get("masterSettings").and_then(|m| m.get("resetRequiresThreshold")).and_then(|b| b.as_bool()).unwrap_or(true)
+}
+
+// ADC threshold lookup: `adcThresholds[masterType]` (case-insensitive) > `adcThreshold` >
+// built-in default (AIPHONE 150, others 70). Configured values are clamped to 0..=MAX_ADC.
+fn trigger_threshold(v: &Value) -> i32 {
+  let master_type = read_master_type(v);
+  let settings = v.get("masterSettings");
+  let per_type = settings
+    .and_then(|m| m.get("adcThresholds"))
+    .and_then(|t| t.as_object())
+    .and_then(|t| t.iter().find(|(k, _)| k.eq_ignore_ascii_case(master_type.name())).map(|(_, n)| n));
+  let configured = per_type.or_else(|| settings.and_then(|m| m.get("adcThreshold"))).and_then(|n| n.as_i64());
+  match configured {
+    Some(n) => n.clamp(0, MAX_ADC) as i32,
+    None => master_type.default_threshold(),
+  }
+}
+
+/// Which side of the threshold counts as a call; `Below` is for active-low wiring.
+#[derive(Clone, Copy, PartialEq)]
+enum ThresholdDirection {
+  Above,
+  Below,
+}
+
+impl ThresholdDirection {
+  fn passes(self, adc: i32, threshold: i32) -> bool {
+    match self {
+      ThresholdDirection::Above => adc >= threshold,
+      ThresholdDirection::Below => adc <= threshold,
+    }
+  }
+}
+
+fn read_threshold_direction(v: &Value) -> ThresholdDirection {
+  let dir = v.get("masterSettings").and_then(|m| m.get("thresholdDirection")).and_then(|s| s.as_str()).unwrap_or("above");
+  if dir.trim().eq_ignore_ascii_case("below") { ThresholdDirection::Below } else { ThresholdDirection::Above }
+}
+
+// Hysteresis: a code only fires after `triggerConsecutive` readings past the threshold, each
+// within `triggerWindowMs` of the previous one. The default of 1 keeps single-reading behavior.
+fn read_hysteresis(v: &Value) -> (u32, u128) {
+  let settings = v.get("masterSettings");
+  let needed = settings.and_then(|m| m.get("triggerConsecutive")).and_then(|n| n.as_u64()).unwrap_or(1).clamp(1, 20) as u32;
+  let window_ms = settings.and_then(|m| m.get("triggerWindowMs")).and_then(|n| n.as_u64()).unwrap_or(500).clamp(50, 10_000) as u128;
+  (needed, window_ms)
+}
+
+fn confirm_reading(v: &Value, code: &str) -> bool {
+  let (needed, window_ms) = read_hysteresis(v);
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+  let mut g = RECENT_READINGS.lock().unwrap_or_else(|e| e.into_inner());
+  let entry = g.entry(code.to_string()).or_insert((0, now));
+  entry.0 = if now.saturating_sub(entry.1) <= window_ms { entry.0.saturating_add(1) } else { 1 };
+  entry.1 = now;
+  entry.0 >= needed
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseRules {
+  master_type: String,
+  code_length_min: usize,
+  code_length_max: usize,
+  line_delimiters: Vec<String>,
+  field_separator: String,
+  field_order: String,
+  reset_prefix: String,
+  reset_target_prefix: String,
+  reset_keep_digits: usize,
+  standby_token: String,
+  standby_reset_pulses: u32,
+  threshold: i32,
+  threshold_direction: String,
+  trigger_consecutive: u32,
+  trigger_window_ms: u128,
+}
+
+fn parse_rules(v: &Value) -> ParseRules {
+  let master_type = read_master_type(v);
+  let mapping = read_code_mapping(v);
+  let (trigger_consecutive, trigger_window_ms) = read_hysteresis(v);
+  let direction = match read_threshold_direction(v) { ThresholdDirection::Above => "above", ThresholdDirection::Below => "below" };
+  ParseRules {
+    master_type: master_type.name().to_string(),
+    code_length_min: 3,
+    code_length_max: 3,
+    line_delimiters: vec!["\n".into(), "\r".into()],
+    field_separator: ":".into(),
+    field_order: match read_field_order(v) { FieldOrder::CodeFirst => "code-first", FieldOrder::ValueFirst => "value-first" }.into(),
+    reset_prefix: mapping.reset_prefix,
+    reset_target_prefix: mapping.trigger_prefix,
+    reset_keep_digits: mapping.keep_digits,
+    standby_token: format!("{}:", mapping.standby_code),
+    standby_reset_pulses: read_tuning(v).standby_reset_pulses,
+    threshold: trigger_threshold(v),
+    threshold_direction: direction.into(),
+    trigger_consecutive,
+    trigger_window_ms,
+  }
+}
+
+/// The rules the read loop currently applies to incoming lines, with config merged over defaults.
+#[tauri::command]
+pub fn get_parse_rules() -> Result<ParseRules, String> {
+  Ok(parse_rules(&crate::config::read_config()?))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfig {
+  config_path: String,
+  parse_rules: ParseRules,
+  reset_requires_threshold: bool,
+  code_aliases: Value,
+  alias_dedup: bool,
+  safe_mode_crash_limit: usize,
+  safe_mode_window_ms: u128,
+  disconnect_grace_ms: u128,
+  trigger_dedup_ms: u128,
+  error_throttle_ms: u128,
+  max_open_retries: Option<u32>,
+  standby_timeout_ms: Option<u128>,
+  frame_dedup_ms: u128,
+  line_idle_ms: u128,
+  default_baud: u32,
+  device_bauds: Value,
+  last_params: Option<SerialParams>,
+}
+
+/// Read-only view of the settings the serial worker resolves on its next reading: config.json
+/// `masterSettings` and the serial store merged over defaults by the same readers it uses.
+/// Config is re-read on every call, so live edits show up immediately.
+#[tauri::command]
+pub fn effective_config(app: AppHandle) -> Result<EffectiveConfig, String> {
+  let v = crate::config::read_config()?;
+  let (safe_mode_crash_limit, safe_mode_window_ms) = read_safe_mode(&v);
+  let tuning = read_tuning(&v);
+  let device_bauds = app.store(SERIAL_STORE).ok().and_then(|s| s.get("deviceBauds")).unwrap_or_else(|| json!({}));
+  Ok(EffectiveConfig {
+    config_path: crate::config::config_path(),
+    parse_rules: parse_rules(&v),
+    reset_requires_threshold: read_reset_requires_threshold(&v),
+    code_aliases: v.get("masterSettings").and_then(|m| m.get("codeAliases")).cloned().unwrap_or_else(|| json!({})),
+    alias_dedup: read_alias_dedup(&v),
+    safe_mode_crash_limit,
+    safe_mode_window_ms,
+    disconnect_grace_ms: disconnect_grace_ms(&v),
+    trigger_dedup_ms: tuning.trigger_dedup_ms,
+    error_throttle_ms: tuning.error_throttle_ms,
+    max_open_retries: tuning.max_open_retries,
+    standby_timeout_ms: tuning.standby_timeout_ms,
+    frame_dedup_ms: tuning.frame_dedup_ms,
+    line_idle_ms: tuning.line_idle_ms,
+    default_baud: DEFAULT_BAUD,
+    device_bauds,
+    last_params: stored_last_params(&app),
+  })
+}
+
+// A mute entry is a bare string (until unmuted) or { "<key>": ..., "until": RFC3339 }; expired
+// entries no longer count.
+fn mute_entry_matches(entry: &Value, key: &str, value: &str, now: chrono::DateTime<chrono::Utc>) -> bool {
+  match entry {
+    Value::String(s) => s == value,
+    Value::Object(o) => o.get(key).and_then(|s| s.as_str()) == Some(value)
+      && o.get("until").and_then(|s| s.as_str()).is_none_or(|until| {
+        chrono::DateTime::parse_from_rfc3339(until).is_ok_and(|t| t > now)
+      }),
+    _ => false,
+  }
+}
+
+/// Whether calls from `code` or `room` are silenced by masterSettings.mutedCodes/mutedRooms.
+pub(crate) fn is_muted(v: &Value, code: &str, room: &str) -> bool {
+  let settings = v.get("masterSettings");
+  let now = chrono::Utc::now();
+  let listed = |list: &str, key: &str, value: &str| !value.is_empty() && settings
+    .and_then(|m| m.get(list))
+    .and_then(|a| a.as_array())
+    .is_some_and(|a| a.iter().any(|e| mute_entry_matches(e, key, value, now)));
+  listed("mutedCodes", "code", code) || listed("mutedRooms", "room", room)
+}
+
+/// Urgency of a call; variants are declared lowest first, so `Ord` ranks them.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+  Low,
+  #[default]
+  Normal,
+  High,
+  Emergency,
+}
+
+impl Priority {
+  fn parse(s: &str) -> Option<Self> {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "low" => Some(Self::Low),
+      "normal" => Some(Self::Normal),
+      "high" => Some(Self::High),
+      "emergency" => Some(Self::Emergency),
+      _ => None,
     }
   }
-  out
+
+  pub(crate) fn as_str(self) -> &'static str {
+    match self { Self::Low => "low", Self::Normal => "normal", Self::High => "high", Self::Emergency => "emergency" }
+  }
 }
 
-pub struct SerialWorker {
-  stop: Arc<AtomicBool>,
-  handle: Option<std::thread::JoinHandle<()>>,
+/// Priority of `code` from masterSettings.codePriorities, an object of code prefix to
+/// "low" | "normal" | "high" | "emergency". The longest matching prefix wins, so with
+/// `{"1": "high", "105": "low"}` code 105 is low and 101 is high. Unmapped codes are normal.
+pub(crate) fn resolve_priority(v: &Value, code: &str) -> Priority {
+  let Some(map) = v.get("masterSettings").and_then(|m| m.get("codePriorities")).and_then(|m| m.as_object()) else { return Priority::default() };
+  map.iter()
+    .filter(|(prefix, _)| !prefix.is_empty() && code.starts_with(prefix.as_str()))
+    .filter_map(|(prefix, level)| level.as_str().and_then(Priority::parse).map(|p| (prefix.len(), p)))
+    .max_by_key(|(len, _)| *len)
+    .map(|(_, p)| p)
+    .unwrap_or_default()
 }
 
-impl SerialWorker {
-  pub fn start(app: AppHandle, port_name: String) -> Result<Self, String> {
-    let stop = Arc::new(AtomicBool::new(false));
-    let stop_c = stop.clone();
-    let handle = std::thread::spawn(move || {
-      // retry loop: keep attempting to open the port until stopped
-      'outer: loop {
-        if stop_c.load(Ordering::Relaxed) { break 'outer; }
-        let mut last_active_code: Option<String> = None;
-        let mut awaiting_reset = false;
-        let mut standby_count: u32 = 0;
-        match serialport::new(&port_name, 9600)
-          .timeout(Duration::from_millis(200))
-          .open() {
-            Ok(mut port) => {
-              let _ = app.emit("serial-connected", &port_name);
-              let mut buf = [0u8; 1024];
-              // read loop until error or stop
-              while !stop_c.load(Ordering::Relaxed) {
-                match port.read(&mut buf) {
-                  Ok(n) if n > 0 => {
-                    let s = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = app.emit("serial-data", &s);
-                    // treat 99: as standby pulse
-                    if s.contains("99:") {
-                      let _ = app.emit("serial-standby-ok", &());
-                      if awaiting_reset {
-                        standby_count = standby_count.saturating_add(1);
-                        if standby_count >= 5 {
-                          if let Some(code) = &last_active_code { let _ = complete_latest_for_code(code); }
-                          awaiting_reset = false;
-                        }
-                      }
-                    }
-                    // try parse lines like "<code>: <adc>"
-                    for part in s.split(|c| c == '\n' || c == '\r') {
-                      if let Some((code_str, rest)) = part.split_once(':') {
-                        let code = code_str.trim();
-                        let rest_trim = rest.trim();
-                        // Enclose/response: patterns like "901:" (no ADC required)
-                        if code.len() == 3 && code.starts_with("90") && code.chars().all(|c| c.is_ascii_digit()) && rest_trim.is_empty() {
-                          let _ = handle_enclose(&app, code);
-                          awaiting_reset = false;
-                          continue;
-                        }
-                        // Valid trigger with ADC
-                        let val = rest_trim.split_whitespace().next().unwrap_or("");
-                        if code.len() == 3 && code.chars().all(|c| c.is_ascii_digit()) && val.chars().all(|c| c.is_ascii_digit()) {
-                          let adc: i32 = val.parse().unwrap_or(0);
-                          if code.starts_with("90") { awaiting_reset = false; }
-                          handle_trigger(&app, code, adc);
-                          if !code.starts_with("90") {
-                            last_active_code = Some(code.to_string());
-                            awaiting_reset = true; standby_count = 0;
-                          }
-                        }
-                      }
-                    }
-                  }
-                  Ok(_) => {}
-                  Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                  Err(_e) => { break; }
-                }
-              }
-              // leaving read loop: disconnected or stopped
-              let _ = app.emit("serial-disconnected", &());
-              // slight delay before retrying
-              std::thread::sleep(Duration::from_millis(800));
-            }
-            Err(e) => {
-              // emit throttled error and retry
-              if should_emit(&format!("open_err:{}", port_name), 3000) {
-                let _ = app.emit("serial-error", &format!("{} (retrying)", e));
-              }
-              // backoff before retrying
-              std::thread::sleep(Duration::from_millis(1000));
-            }
-          }
-      }
+/// A call record's stored `priority`; records from before priorities existed are resolved now.
+pub(crate) fn record_priority(v: &Value, rec: &Value) -> Priority {
+  rec.get("priority").and_then(|s| s.as_str()).and_then(Priority::parse)
+    .unwrap_or_else(|| resolve_priority(v, rec.get("code").and_then(|s| s.as_str()).unwrap_or("")))
+}
+
+/// Silences (or un-silences) calls from `code`: they are still recorded and shown, but arrive
+/// as `nurse-call-muted` instead of the sound-playing `nurse-call`. With `minutes` the mute
+/// lifts by itself. Saved in masterSettings.mutedCodes, so it survives restarts.
+#[tauri::command]
+pub fn mute_code(code: String, muted: bool, minutes: Option<u64>) -> Result<(), String> {
+  let _guard = crate::config::lock_config();
+  let mut v = crate::config::read_config()?;
+  let now = chrono::Utc::now();
+  let settings = v.as_object_mut().ok_or("config.json is not an object")?
+    .entry("masterSettings").or_insert_with(|| json!({}))
+    .as_object_mut().ok_or("masterSettings is not an object")?;
+  let list = settings.entry("mutedCodes").or_insert_with(|| json!([]));
+  if !list.is_array() { *list = json!([]); }
+  let arr = list.as_array_mut().ok_or("mutedCodes is not an array")?;
+  // drop this code's old entries and anything that has expired
+  arr.retain(|e| {
+    let entry_code = e.as_str().or_else(|| e.get("code").and_then(|s| s.as_str()));
+    entry_code.is_some_and(|c| c != code && mute_entry_matches(e, "code", c, now))
+  });
+  if muted {
+    arr.push(match minutes.filter(|m| *m > 0) {
+      Some(m) => json!({ "code": code, "until": (now + chrono::Duration::minutes(m.min(24 * 60) as i64)).to_rfc3339_opts(SecondsFormat::Secs, true) }),
+      None => Value::String(code),
     });
-    Ok(Self { stop, handle: Some(handle) })
   }
+  crate::config::write_config(&v)
+}
 
-  pub fn stop(&mut self) {
-    self.stop.store(true, Ordering::Relaxed);
-    if let Some(h) = self.handle.take() { let _ = h.join(); }
+fn persist_unmapped(v: &Value) -> bool {
+  v.get("masterSettings").and_then(|m| m.get("persistUnmappedCodes")).and_then(|b| b.as_bool()).unwrap_or(false)
+}
+
+// The caller holds the config lock; with masterSettings.persistUnmappedCodes on, the set is
+// mirrored into `unmappedCodesSeen` ({code: {count, lastSeen}}) and written with `v`.
+fn note_unmapped(v: &mut Value, code: &str) {
+  let (count, last_seen) = {
+    let mut g = UNMAPPED_SEEN.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = g.entry(code.to_string()).or_insert((0, String::new()));
+    entry.0 += 1;
+    entry.1 = now_iso();
+    entry.clone()
+  };
+  if !persist_unmapped(v) { return; }
+  let Some(obj) = v.as_object_mut() else { return };
+  let seen = obj.entry("unmappedCodesSeen").or_insert_with(|| json!({}));
+  if !seen.is_object() { *seen = json!({}); }
+  seen[code] = json!({ "count": count, "lastSeen": last_seen });
+  if let Err(e) = crate::config::write_config(v) { log::warn!("config write failed: {}", e); }
+}
+
+/// Called from setup: with masterSettings.persistUnmappedCodes on, reloads the codes saved
+/// by earlier runs.
+pub(crate) fn load_unmapped_seen(v: &Value) {
+  if !persist_unmapped(v) { return; }
+  let Some(seen) = v.get("unmappedCodesSeen").and_then(|o| o.as_object()) else { return };
+  let mut g = UNMAPPED_SEEN.lock().unwrap_or_else(|e| e.into_inner());
+  for (code, entry) in seen {
+    let count = entry.get("count").and_then(|n| n.as_u64()).unwrap_or(0);
+    let last_seen = entry.get("lastSeen").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    g.insert(code.clone(), (count, last_seen));
   }
 }
 
-fn now_iso() -> String { chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true) }
-fn now_local_compact() -> String { Local::now().format("%H:%M:%S.%-m-%-d-%Y").to_string() }
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmappedCode {
+  code: String,
+  count: u64,
+  last_seen: String,
+}
 
-fn read_master_type(v: &Value) -> String {
-  v.get("masterSettings")
-    .and_then(|m| m.get("masterType").or_else(|| m.get("master")).or_else(|| m.get("type")))
-    .and_then(|s| s.as_str())
-    .unwrap_or("Commax")
-    .trim()
-    .to_string()
+/// Codes that fired without a masterData entry since startup (or the last clear), to help
+/// installers find buttons that were never configured. Kept across restarts when
+/// masterSettings.persistUnmappedCodes is on.
+#[tauri::command]
+pub fn unmapped_codes_seen() -> Vec<UnmappedCode> {
+  let g = UNMAPPED_SEEN.lock().unwrap_or_else(|e| e.into_inner());
+  let mut out: Vec<UnmappedCode> = g.iter()
+    .map(|(code, (count, last_seen))| UnmappedCode { code: code.clone(), count: *count, last_seen: last_seen.clone() })
+    .collect();
+  out.sort_by(|a, b| a.code.cmp(&b.code));
+  out
+}
+
+#[tauri::command]
+pub fn clear_unmapped_seen() -> Result<(), String> {
+  UNMAPPED_SEEN.lock().unwrap_or_else(|e| e.into_inner()).clear();
+  let _guard = crate::config::lock_config();
+  let mut v = crate::config::read_config()?;
+  match v.as_object_mut().and_then(|o| o.remove("unmappedCodesSeen")) {
+    Some(_) => crate::config::write_config(&v),
+    None => Ok(()),
+  }
+}
+
+// Feeds the running diagnose session; true when `code` is the one being diagnosed.
+fn record_diagnose(app: &impl EventSink, code: &str, adc: i32, line: &str) -> bool {
+  let mut g = DIAGNOSE.lock().unwrap_or_else(|e| e.into_inner());
+  let Some(session) = g.as_mut().filter(|d| d.code == code) else { return false };
+  let hit = DiagnoseHit {
+    line: line.trim().to_string(),
+    adc,
+    threshold: session.threshold,
+    passes: session.direction.passes(adc, session.threshold),
+    time: now_iso(),
+  };
+  emit_event(app, EventScope::Diagnostic, "serial-diagnose", &hit);
+  session.hits.push(hit);
+  true
 }
 
-fn handle_trigger(app: &AppHandle, code: &str, adc: i32) {
-  let cfg_path = "/Users/maul/github/modern-desktop-app-template/public/config.json";
-  let cfg_text = match fs::read_to_string(cfg_path) { Ok(t) => t, Err(_) => return };
-  let mut v: Value = match serde_json::from_str(&cfg_text) { Ok(j) => j, Err(_) => return };
-  let master_type = read_master_type(&v);
-  let threshold = if master_type.eq_ignore_ascii_case("AIPHONE") { 150 } else { 70 };
-  if adc < threshold { return; }
+/// Commissioning aid: for `seconds`, reports every line for `code` seen by the connected
+/// worker with its ADC and whether it clears the current threshold. While the session runs,
+/// presses of `code` are only reported here (and live as `serial-diagnose`): they are not
+/// recorded in callHistoryStorage and raise no nurse-call.
+#[tauri::command]
+pub async fn serial_diagnose_code(code: String, seconds: u64) -> Result<Vec<DiagnoseHit>, String> {
+  let v = crate::config::read_config()?;
+  let code = code.trim().to_string();
+  {
+    let mut g = DIAGNOSE.lock().unwrap_or_else(|e| e.into_inner());
+    if g.is_some() { return Err("a diagnosis is already running".into()); }
+    *g = Some(DiagnoseSession { code, threshold: trigger_threshold(&v), direction: read_threshold_direction(&v), hits: Vec::new() });
+  }
+  // ends the session even if this command is dropped mid-sleep, so the next one can start
+  let guard = DiagnoseGuard;
+  tokio::time::sleep(Duration::from_secs(seconds.clamp(1, 120))).await;
+  Ok(guard.finish())
+}
+
+struct DiagnoseGuard;
+
+impl DiagnoseGuard {
+  fn finish(self) -> Vec<DiagnoseHit> { take_diagnose().map(|d| d.hits).unwrap_or_default() }
+}
+
+impl Drop for DiagnoseGuard {
+  fn drop(&mut self) { take_diagnose(); }
+}
+
+fn take_diagnose() -> Option<DiagnoseSession> { DIAGNOSE.lock().unwrap_or_else(|e| e.into_inner()).take() }
+
+// A missing or corrupt config.json drops readings, so tell the frontend instead of failing silently.
+fn report_config_error(app: &impl EventSink, err: &str) {
+  if should_emit("config_err", 5000) {
+    let path = crate::config::config_path();
+    let kind = if std::path::Path::new(&path).exists() { ErrorKind::ConfigParse } else { ErrorKind::ConfigMissing };
+    emit_serial_error(app, kind, "", format!("config.json unreadable ({}): {}", path, err), false);
+  }
+}
+
+fn handle_trigger(app: &impl EventSink, code: &str, adc: i32, tuning: &SerialTuning) {
+  let _guard = crate::config::lock_config();
+  let mut v = match crate::config::read_config() { Ok(v) => v, Err(e) => { report_config_error(app, &e); return } };
+  let threshold = trigger_threshold(&v);
+  let direction = read_threshold_direction(&v);
+  let reset_target = read_code_mapping(&v).resolve_reset_target(code);
+  let is_reset = reset_target.is_some();
+  // sites with weak reset wiring can let any 90x pulse through
+  if !direction.passes(adc, threshold) && (!is_reset || read_reset_requires_threshold(&v)) {
+    // a reading back at rest breaks any run of consecutive readings
+    RECENT_READINGS.lock().unwrap_or_else(|e| e.into_inner()).remove(code);
+    return;
+  }
 
   // reset code pattern: 90x maps to 10x
-  if code.starts_with("90") && code.len() == 3 {
-    let last = &code[2..];
-    let target = format!("10{}", last);
-    if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
-      // find latest active with target code
-      if let Some(pos) = arr.iter().rposition(|rec| rec.get("code").and_then(|s| s.as_str()) == Some(target.as_str()) && rec.get("status").and_then(|s| s.as_str()) != Some("completed")) {
-        if let Some(obj) = arr.get_mut(pos).and_then(|r| r.as_object_mut()) {
-          let iso = now_iso();
-          obj.insert("status".into(), Value::String("completed".into()));
-          obj.insert("resetTime".into(), Value::String(iso.clone()));
-          obj.insert("resetTimeStr".into(), Value::String(now_local_compact()));
-          obj.insert("dateModified".into(), Value::String(iso));
-          let _ = fs::write(cfg_path, serde_json::to_string_pretty(&v).unwrap());
-        }
-      }
+  if let Some(target) = reset_target {
+    let target = logical_code(&v, &target);
+    // latest open record with the target code
+    if complete_latest_where(&mut v, |rec| rec.get("code").and_then(|s| s.as_str()) == Some(target.as_str())).is_some() {
+      if let Err(e) = crate::config::write_config(&v) { log::warn!("config write failed: {}", e); }
     }
     return;
   }
 
-  if adc < threshold { return; }
+  if !confirm_reading(&v, code) { return; }
+
+  // aliased codes (pull-cord vs bedside) share the canonical code's mapping
+  let canonical = resolve_alias(&v, code);
+  if find_master(&v, &canonical).is_none() { note_unmapped(&mut v, code); }
+  let master = find_master(&v, &canonical);
+  // out-of-service buttons keep their mapping but record nothing
+  if master.and_then(|r| r.get("enabled")).and_then(|b| b.as_bool()) == Some(false) {
+    if should_emit(&format!("disabled:{}", code), tuning.error_throttle_ms) {
+      emit_event(app, EventScope::Call, "nurse-call-disabled", &json!({ "code": code, "adc": adc }));
+    }
+    return;
+  }
+  let logical = logical_code(&v, code);
+  let code = logical.as_str();
 
-  // De-dup: if there is already an active record for this code, do not append or emit again
+  // De-dup: if there is already an open record (active or acknowledged) for this code, do not append or emit again
   if let Some(arr) = v.get("callHistoryStorage").and_then(|a| a.as_array()) {
-    let exists_active = arr.iter().any(|rec|
-      rec.get("code").and_then(|s| s.as_str()) == Some(code)
-        && rec.get("status").and_then(|s| s.as_str()) != Some("completed")
-    );
+    let exists_active = arr.iter().any(|rec| rec.get("code").and_then(|s| s.as_str()) == Some(code) && is_open(rec));
     if exists_active { return; }
   }
 
   let mut room = String::new();
   let mut bed = String::new();
   let mut files: Vec<String> = Vec::new();
-  if let Some(md) = v.get("masterData").and_then(|a| a.as_array()) {
-    for r in md {
-      if r.get("charCode").and_then(|s| s.as_str()) == Some(code) {
-        room = r.get("roomName").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        bed = r.get("bedName").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        for key in ["v1","v2","v3","v4","v5","v6"] {
-          if let Some(f) = r.get(key).and_then(|s| s.as_str()) {
-            if !f.is_empty() && f != "-" { files.push(f.to_string()); }
-          }
-        }
-        break;
-      }
-    }
+  if let Some(r) = find_master(&v, &canonical) {
+    room = r.get("roomName").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    bed = r.get("bedName").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    files = master_files(r);
   }
+  let (files, missing_audio) = split_audio(&v, files);
   let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.to_string() };
+  let priority = resolve_priority(&v, code);
   let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
-  let iso = now_iso();
-  let rec = json!({
+  let (iso, iso_ms) = now_stamp();
+  let mut rec = json!({
     "id": now,
     "code": code,
     "room": room,
@@ -199,87 +1764,107 @@ fn handle_trigger(app: &AppHandle, code: &str, adc: i32) {
     "display": display,
     "time": now_local_compact(),
     "timestamp": iso,
+    "timestampMs": iso_ms,
     "status": "active",
+    "priority": priority,
+    "adc": adc,
     "dateAdded": iso,
     "dateModified": iso
   });
+  let muted = is_muted(&v, code, &room);
+  if muted { rec["muted"] = Value::Bool(true); }
+  crate::call_log::append("trigger", &rec);
   // append to callHistoryStorage
   if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
     arr.push(rec);
-    let _ = fs::write(cfg_path, serde_json::to_string_pretty(&v).unwrap());
+    if let Err(e) = crate::config::write_config(&v) { log::warn!("config write failed: {}", e); }
   }
-  if should_emit(&format!("trigger:{}", code), 1500) {
+  if should_emit(&format!("trigger:{}", code), tuning.trigger_dedup_ms) {
+    // a deleted or mistyped file would otherwise just fail silently at playback
+    if !missing_audio.is_empty() {
+      emit_event(app, EventScope::Diagnostic, "serial-audio-missing", &json!({ "code": code, "files": missing_audio }));
+    }
+    // muted: shown and tracked by the frontend, but never played
+    if muted {
+      emit_event(app, EventScope::Call, "nurse-call-muted", &json!({ "code": code, "room": room, "bed": bed, "display": display, "priority": priority }));
+      return;
+    }
     // emit event for frontend to play sounds and notifications
-    let _ = app.emit("nurse-call", &json!({
+    emit_event(app, EventScope::Call, "nurse-call", &json!({
       "code": code,
       "room": room,
       "bed": bed,
       "display": display,
       "files": files,
+      "priority": priority,
+      "snoozed": crate::history::is_snoozed(),
     }));
   }
 }
 
+// Shared by every completion path: marks the newest open record matching `matches` completed
+// and returns its (code, room, bed). The caller holds the config lock and writes `v` back.
+fn complete_latest_where(v: &mut Value, matches: impl Fn(&Value) -> bool) -> Option<(String, String, String)> {
+  let arr = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut())?;
+  let obj = arr.iter_mut()
+    .rev()
+    .find(|rec| is_open(rec) && matches(rec))
+    .and_then(|r| r.as_object_mut())?;
+  let field = |obj: &serde_json::Map<String, Value>, key: &str| obj.get(key).and_then(|s| s.as_str()).unwrap_or("").to_string();
+  let taken = (field(obj, "code"), field(obj, "room"), field(obj, "bed"));
+  mark_completed(obj);
+  Some(taken)
+}
+
 fn complete_latest_for_code(code: &str) -> Result<(String,String), String> {
-  let cfg_path = "/Users/maul/github/modern-desktop-app-template/public/config.json";
-  let cfg_text = fs::read_to_string(cfg_path).map_err(|e| e.to_string())?;
-  let mut v: Value = serde_json::from_str(&cfg_text).map_err(|e| e.to_string())?;
-  let mut room = String::new();
-  let mut bed = String::new();
-  if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
-    if let Some(pos) = arr.iter().rposition(|rec| rec.get("code").and_then(|s| s.as_str()) == Some(code) && rec.get("status").and_then(|s| s.as_str()) != Some("completed")) {
-      if let Some(obj) = arr.get_mut(pos).and_then(|r| r.as_object_mut()) {
-        let iso = now_iso();
-        obj.insert("status".into(), Value::String("completed".into()));
-        obj.insert("resetTime".into(), Value::String(iso.clone()));
-        obj.insert("resetTimeStr".into(), Value::String(now_local_compact()));
-        obj.insert("dateModified".into(), Value::String(iso));
-        if let Some(r) = obj.get("room").and_then(|s| s.as_str()) { room = r.to_string(); }
-        if let Some(b) = obj.get("bed").and_then(|s| s.as_str()) { bed = b.to_string(); }
-        fs::write(cfg_path, serde_json::to_string_pretty(&v).unwrap()).map_err(|e| e.to_string())?;
-      }
-    }
+  let _guard = crate::config::lock_config();
+  let mut v = crate::config::read_config()?;
+  let logical = logical_code(&v, code);
+  match complete_latest_where(&mut v, |rec| rec.get("code").and_then(|s| s.as_str()) == Some(logical.as_str())) {
+    Some((_, room, bed)) => { crate::config::write_config(&v)?; Ok((room, bed)) }
+    None => Ok((String::new(), String::new())),
   }
-  Ok((room, bed))
 }
 
-fn handle_enclose(app: &AppHandle, code90: &str) -> Result<(), String> {
-  // Map 90x -> 10x
-  let mut chars = code90.chars();
-  let _ = chars.next(); let _ = chars.next();
-  let last = chars.next().unwrap_or('0');
-  let target = format!("10{}", last);
-  if let Ok((room, bed)) = complete_latest_for_code(&target) {
-    let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { target.clone() };
-    if should_emit(&format!("enclose:{}", target), 1500) {
-      // app notification/event only; frontend will also raise OS notification
-      let _ = app.emit("nurse-call-response", &json!({ "code": target, "room": room, "bed": bed, "display": display }));
-    }
+// `target` is the call code the reset maps to (90x -> 10x), see CodeMapping::resolve_reset_target.
+fn handle_enclose(app: &impl EventSink, target: &str, tuning: &SerialTuning) -> Result<(), String> {
+  let (room, bed) = complete_latest_for_code(target).inspect_err(|e| report_config_error(app, e))?;
+  let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { target.to_string() };
+  if should_emit(&format!("enclose:{}", target), tuning.trigger_dedup_ms) {
+    // app notification/event only; frontend will also raise OS notification
+    emit_event(app, EventScope::Call, "nurse-call-response", &json!({ "code": target, "room": room, "bed": bed, "display": display }));
   }
   Ok(())
 }
 
 fn complete_latest_any() -> Result<(String,String,String), String> {
-  let cfg_path = "/Users/maul/github/modern-desktop-app-template/public/config.json";
-  let cfg_text = fs::read_to_string(cfg_path).map_err(|e| e.to_string())?;
-  let mut v: Value = serde_json::from_str(&cfg_text).map_err(|e| e.to_string())?;
-  if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
-    if let Some(pos) = arr.iter().rposition(|rec| rec.get("status").and_then(|s| s.as_str()) != Some("completed")) {
-      if let Some(obj) = arr.get_mut(pos).and_then(|r| r.as_object_mut()) {
-        let code = obj.get("code").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let room = obj.get("room").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let bed  = obj.get("bed").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let iso = now_iso();
-        obj.insert("status".into(), Value::String("completed".into()));
-        obj.insert("resetTime".into(), Value::String(iso.clone()));
-        obj.insert("resetTimeStr".into(), Value::String(now_local_compact()));
-        obj.insert("dateModified".into(), Value::String(iso));
-        fs::write(cfg_path, serde_json::to_string_pretty(&v).unwrap()).map_err(|e| e.to_string())?;
-        return Ok((code, room, bed));
+  let _guard = crate::config::lock_config();
+  let mut v = crate::config::read_config()?;
+  let taken = complete_latest_where(&mut v, |_| true).ok_or("no pending calls")?;
+  crate::config::write_config(&v)?;
+  Ok(taken)
+}
+
+/// Completes one specific call by its record `id` (e.g. an older call closed from the UI),
+/// stamped like the panel reset, and emits `nurse-call-response`. Errors if `id` is unknown
+/// or already completed.
+#[tauri::command]
+pub fn call_complete(app: AppHandle, id: i64) -> Result<(), String> {
+  let (code, room, bed) = {
+    let _guard = crate::config::lock_config();
+    let mut v = crate::config::read_config()?;
+    let has_id = |rec: &Value| rec.get("id").and_then(|n| n.as_i64()) == Some(id);
+    match complete_latest_where(&mut v, has_id) {
+      Some(taken) => { crate::config::write_config(&v)?; taken }
+      None => {
+        let exists = v.get("callHistoryStorage").and_then(|a| a.as_array()).is_some_and(|arr| arr.iter().any(has_id));
+        return Err(if exists { format!("call {} is already completed", id) } else { format!("call {} not found", id) });
       }
     }
-  }
-  Err("no pending calls".into())
+  };
+  let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.clone() };
+  emit_event(&app, EventScope::Call, "nurse-call-response", &json!({ "code": code, "room": room, "bed": bed, "display": display }));
+  Ok(())
 }
 
 #[tauri::command]
@@ -287,7 +1872,7 @@ pub fn serial_enclose_latest(app: AppHandle) -> Result<(), String> {
   match complete_latest_any() {
     Ok((code, room, bed)) => {
       let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.clone() };
-      let _ = app.emit("nurse-call-response", &json!({"code": code, "display": display}));
+      emit_event(&app, EventScope::Call, "nurse-call-response", &json!({"code": code, "display": display}));
       Ok(())
     }
     Err(e) => Err(e)
@@ -296,36 +1881,708 @@ pub fn serial_enclose_latest(app: AppHandle) -> Result<(), String> {
 
 #[tauri::command]
 pub fn serial_enclose_all(app: AppHandle) -> Result<u32, String> {
-  let cfg_path = "/Users/maul/github/modern-desktop-app-template/public/config.json";
-  let cfg_text = fs::read_to_string(cfg_path).map_err(|e| e.to_string())?;
-  let mut v: Value = serde_json::from_str(&cfg_text).map_err(|e| e.to_string())?;
-  let mut updated: u32 = 0;
-  let mut responses: Vec<(String, String)> = Vec::new(); // (code, display)
-  if let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) {
-    for rec in arr.iter_mut() {
-      let status = rec.get("status").and_then(|s| s.as_str()).unwrap_or("");
-      if status != "completed" {
-        let code = rec.get("code").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let room = rec.get("room").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let bed  = rec.get("bed").and_then(|s| s.as_str()).unwrap_or("").to_string();
-        let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.clone() };
-        let iso = now_iso();
-        if let Some(obj) = rec.as_object_mut() {
-          obj.insert("status".into(), Value::String("completed".into()));
-          obj.insert("resetTime".into(), Value::String(iso.clone()));
-          obj.insert("resetTimeStr".into(), Value::String(now_local_compact()));
-          obj.insert("dateModified".into(), Value::String(iso));
-        }
-        responses.push((code, display));
-        updated += 1;
-      }
+  let responses = {
+    let _guard = crate::config::lock_config();
+    let mut v = crate::config::read_config()?;
+    let responses = complete_all(&mut v);
+    // single write first so the file is consistent even if the UI only sees part of the progress
+    if !responses.is_empty() { crate::config::write_config(&v)?; }
+    responses
+  };
+  let updated = responses.len() as u32;
+  emit_enclose_responses(&app, responses);
+  Ok(updated)
+}
+
+/// Marks every open call in `v` completed; the caller holds the config lock and writes `v`.
+/// Returns (code, display) per completed call for `emit_enclose_responses`.
+pub(crate) fn complete_all(v: &mut Value) -> Vec<(String, String)> {
+  let mut responses = Vec::new();
+  let Some(arr) = v.get_mut("callHistoryStorage").and_then(|a| a.as_array_mut()) else { return responses };
+  for rec in arr.iter_mut().filter(|rec| is_open(rec)) {
+    let code = rec.get("code").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    let room = rec.get("room").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    let bed  = rec.get("bed").and_then(|s| s.as_str()).unwrap_or("").to_string();
+    let display = if !room.is_empty() { format!("{} - {}", room, bed) } else { code.clone() };
+    if let Some(obj) = rec.as_object_mut() { mark_completed(obj); }
+    responses.push((code, display));
+  }
+  responses
+}
+
+/// `serial-enclose-progress` plus one `nurse-call-response` per call from `complete_all`.
+pub(crate) fn emit_enclose_responses(app: &impl EventSink, responses: Vec<(String, String)>) {
+  let total = responses.len();
+  if total == 0 { return; }
+  emit_event(app, EventScope::Call, "serial-enclose-progress", &json!({"current": 0, "total": total}));
+  for (i, (code, display)) in responses.into_iter().enumerate() {
+    emit_event(app, EventScope::Call, "nurse-call-response", &json!({"code": code, "display": display}));
+    emit_event(app, EventScope::Call, "serial-enclose-progress", &json!({"current": i + 1, "total": total}));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::test_support::use_config;
+  use crate::utils::RecordingSink;
+
+  // Trigger and enclose de-dup (should_emit) is process-wide, so each test sticks to its own
+  // codes; only 101..=109 can be reset with the default 90x -> 10x mapping.
+  fn site(beds: &[(&str, &str, &str)], settings: Value, history: Value) -> Value {
+    let md: Vec<Value> = beds.iter().map(|(code, room, bed)| json!({ "charCode": code, "roomName": room, "bedName": bed })).collect();
+    json!({ "masterSettings": settings, "masterData": md, "callHistoryStorage": history })
+  }
+
+  const CALL_EVENTS: [&str; 2] = ["nurse-call", "nurse-call-response"];
+
+  fn processor(v: &Value) -> FrameProcessor {
+    FrameProcessor::new("test", read_code_mapping(v), read_field_order(v), read_tuning(v))
+  }
+
+  fn history() -> Vec<Value> {
+    crate::config::read_config().unwrap()["callHistoryStorage"].as_array().cloned().unwrap_or_default()
+  }
+
+  fn nurse_call(code: &str, room: &str, bed: &str) -> (String, Value) {
+    ("nurse-call".into(), json!({ "code": code, "room": room, "bed": bed, "display": format!("{} - {}", room, bed), "files": [], "priority": "normal", "snoozed": false }))
+  }
+
+  fn response(code: &str, room: &str, bed: &str) -> (String, Value) {
+    ("nurse-call-response".into(), json!({ "code": code, "room": room, "bed": bed, "display": format!("{} - {}", room, bed) }))
+  }
+
+  #[test]
+  fn trigger_then_reset_emits_call_then_response() {
+    let v = site(&[("101", "Mawar", "1")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "101: 85\n");
+    frames.process_frame(&sink, "901:\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("101", "Mawar", "1"), response("101", "Mawar", "1")]);
+    let recs = history();
+    assert_eq!(recs.len(), 1);
+    assert_eq!(recs[0]["status"], "completed");
+  }
+
+  #[test]
+  fn resets_complete_their_own_call() {
+    let v = site(&[("102", "Melati", "2"), ("103", "Anggrek", "3")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "102: 90\r\n103: 91\r\n");
+    frames.process_frame(&sink, "903:\r\n");
+    frames.process_frame(&sink, "902:\r\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![
+      nurse_call("102", "Melati", "2"),
+      nurse_call("103", "Anggrek", "3"),
+      response("103", "Anggrek", "3"),
+      response("102", "Melati", "2"),
+    ]);
+    assert!(history().iter().all(|rec| rec["status"] == "completed"));
+  }
+
+  #[test]
+  fn open_call_is_not_emitted_twice() {
+    let v = site(&[("111", "Dahlia", "1")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "111: 85\n");
+    frames.process_frame(&sink, "111: 86\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("111", "Dahlia", "1")]);
+    assert_eq!(history().len(), 1);
+  }
+
+  #[test]
+  fn reading_below_threshold_emits_nothing() {
+    let v = site(&[("112", "Dahlia", "2")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    processor(&v).process_frame(&sink, "112: 20\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert!(history().is_empty());
+  }
+
+  #[test]
+  fn default_mapping_resolves_reset_targets() {
+    let mapping = CodeMapping::default();
+    assert_eq!(mapping.resolve_reset_target("901"), Some("101".to_string()));
+    assert_eq!(mapping.resolve_reset_target("905"), Some("105".to_string()));
+  }
+
+  #[test]
+  fn mapping_defaults_follow_the_master_type() {
+    for name in ["Commax", "AIPHONE", "SomePanel"] {
+      let v = json!({ "masterSettings": { "masterType": name, "codeMapping": { "keepDigits": 2 } } });
+      let master = MasterType::parse(name);
+      let mapping = read_code_mapping(&v);
+      let (reset, call) = master.reset_mapping();
+      assert_eq!(mapping.resolve_reset_target(&format!("{}12", reset)), Some(format!("{}12", call)), "{}", name);
+      assert_eq!(mapping.standby_code, master.standby_code());
+      assert_eq!(parse_frame(&format!("{}:", master.standby_code()), &mapping, FieldOrder::CodeFirst), Some(ParsedEvent::Standby));
     }
   }
-  if updated > 0 {
-    fs::write(cfg_path, serde_json::to_string_pretty(&v).unwrap()).map_err(|e| e.to_string())?;
-    for (code, display) in responses {
-      let _ = app.emit("nurse-call-response", &json!({"code": code, "display": display}));
+
+  #[test]
+  fn non_reset_codes_have_no_target() {
+    let mapping = CodeMapping::default();
+    for code in ["101", "99", "9012", "90a", "801"] {
+      assert_eq!(mapping.resolve_reset_target(code), None, "{}", code);
     }
   }
-  Ok(updated)
+
+  #[test]
+  fn configured_mapping_overrides_defaults() {
+    let mapping = read_code_mapping(&json!({ "masterSettings": { "codeMapping": { "resetPrefix": "8", "triggerPrefix": "2", "keepDigits": 2 } } }));
+    assert_eq!(mapping.resolve_reset_target("815"), Some("215".to_string()));
+    assert_eq!(mapping.resolve_reset_target("901"), None);
+  }
+
+  #[test]
+  fn record_without_status_does_not_block_its_code() {
+    let old = json!({ "id": 1, "code": "147", "room": "Kenanga", "bed": "7", "timestamp": "2020-01-01T00:00:00Z" });
+    let v = site(&[("147", "Kenanga", "7")], json!({}), json!([old]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    processor(&v).process_frame(&sink, "147: 85\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("147", "Kenanga", "7")]);
+    let recs = history();
+    assert_eq!(recs.len(), 2);
+    assert_eq!(recs[1]["status"], "active");
+  }
+
+  // de-dup off, so these see exactly what each mode cuts out
+  fn framer(mode: FrameMode) -> Framer { Framer::new(mode, 0, LINE_IDLE_MS) }
+
+  fn out(frames: &[&[u8]]) -> Vec<Vec<u8>> { frames.iter().map(|f| f.to_vec()).collect() }
+
+  #[test]
+  fn line_text_joins_a_line_split_across_reads() {
+    let mut f = framer(FrameMode::LineText);
+    assert_eq!(f.push("test", b"10", 0), out(&[]));
+    assert_eq!(f.push("test", b"1: 85\r\n102: 9", 1), out(&[b"101: 85"]));
+    assert_eq!(f.buffered(), 6);
+    assert_eq!(f.push("test", b"0\n", 2), out(&[b"102: 90"]));
+    assert_eq!(f.buffered(), 0);
+  }
+
+  #[test]
+  fn line_text_flushes_an_unterminated_line_when_idle() {
+    let mut f = framer(FrameMode::LineText);
+    assert_eq!(f.push("test", b"99:", 0), out(&[]));
+    assert_eq!(f.flush_idle(LINE_IDLE_MS - 1), out(&[]));
+    assert_eq!(f.flush_idle(LINE_IDLE_MS), out(&[b"99:"]));
+    assert_eq!(f.flush_idle(LINE_IDLE_MS + 1), out(&[]));
+  }
+
+  #[test]
+  fn delimited_discards_bytes_between_frames() {
+    let mut f = framer(FrameMode::Delimited { start: 0x02, end: 0x03 });
+    assert_eq!(f.push("test", b"junk\x02101: 8", 0), out(&[]));
+    assert_eq!(f.push("test", b"5\x03noise\x02", 1), out(&[b"101: 85"]));
+    assert_eq!(f.buffered(), 1);
+    assert_eq!(f.push("test", b"901:\x03\x0299:\x03", 2), out(&[b"901:", b"99:"]));
+    // an open frame keeps waiting for its end byte
+    assert_eq!(f.push("test", b"\x02102", 3), out(&[]));
+    assert_eq!(f.flush_idle(3 + LINE_IDLE_MS), out(&[]));
+    assert_eq!(f.push("test", b": 90\x03", 5), out(&[b"102: 90"]));
+  }
+
+  #[test]
+  fn delimited_keeps_the_newer_of_two_starts() {
+    let mut f = framer(FrameMode::Delimited { start: 0x02, end: 0x03 });
+    assert_eq!(f.push("test", b"\x02101\x02102: 85\x03", 0), out(&[b"102: 85"]));
+  }
+
+  #[test]
+  fn fixed_length_waits_for_n_bytes() {
+    let mut f = framer(FrameMode::FixedLength { n: 7 });
+    assert_eq!(f.push("test", b"101:", 0), out(&[]));
+    assert_eq!(f.push("test", b" 85102", 1), out(&[b"101: 85"]));
+    assert_eq!(f.buffered(), 3);
+    assert_eq!(f.push("test", b": 90", 2), out(&[b"102: 90"]));
+    // a partial frame is dropped on idle so the next one starts aligned
+    assert_eq!(f.push("test", b"103", 3), out(&[]));
+    assert_eq!(f.flush_idle(4), out(&[]));
+    assert_eq!(f.buffered(), 3);
+    assert_eq!(f.flush_idle(3 + LINE_IDLE_MS), out(&[]));
+    assert_eq!(f.buffered(), 0);
+  }
+
+  #[test]
+  fn fixed_length_frame_keeps_newline_bytes() {
+    let mut f = framer(FrameMode::FixedLength { n: 4 });
+    assert_eq!(f.push("test", b"\x01\x0a\x0d", 0), out(&[]));
+    assert_eq!(f.push("test", b"\x02\x0a\x00\x00\x0d", 1), out(&[b"\x01\x0a\x0d\x02", b"\x0a\x00\x00\x0d"]));
+    // de-dup compares whole frames too
+    let mut f = Framer::new(FrameMode::FixedLength { n: 4 }, FRAME_DEDUP_MS, LINE_IDLE_MS);
+    assert_eq!(f.push("test", b"\x01\x0a\x02\x03\x01\x0a\x02\x04", 0), out(&[b"\x01\x0a\x02\x03", b"\x01\x0a\x02\x04"]));
+    assert_eq!(f.push("test", b"\x01\x0a\x02\x03", 1), out(&[]));
+  }
+
+  #[test]
+  fn frame_mode_is_read_from_settings() {
+    let mode = |m: Value| read_frame_mode(&json!({ "masterSettings": { "frameMode": m } }));
+    assert!(read_frame_mode(&json!({})) == FrameMode::LineText);
+    assert!(mode(json!({ "mode": "delimited", "start": 2, "end": 3 })) == FrameMode::Delimited { start: 2, end: 3 });
+    assert!(mode(json!({ "mode": "fixed-length", "n": 8 })) == FrameMode::FixedLength { n: 8 });
+    // malformed settings keep the line default
+    assert!(mode(json!({ "mode": "delimited", "start": 2, "end": 2 })) == FrameMode::LineText);
+    assert!(mode(json!({ "mode": "fixed-length", "n": 0 })) == FrameMode::LineText);
+  }
+
+  #[test]
+  fn identical_frame_inside_dedup_window_is_parsed_once() {
+    let mut f = Framer::new(FrameMode::LineText, FRAME_DEDUP_MS, LINE_IDLE_MS);
+    let mut frames = processor(&json!({}));
+    frames.awaiting_reset = true;
+    let sink = RecordingSink::default();
+    for now in [1_000, 1_010] {
+      frames.process_frames(&sink, &f.push("test", b"99:\n", now));
+    }
+    assert_eq!(frames.standby_count, 1);
+  }
+
+  #[test]
+  fn identical_frame_after_dedup_window_is_parsed_again() {
+    let mut f = Framer::new(FrameMode::LineText, FRAME_DEDUP_MS, LINE_IDLE_MS);
+    let mut frames = processor(&json!({}));
+    frames.awaiting_reset = true;
+    let sink = RecordingSink::default();
+    for now in [1_000, 1_000 + FRAME_DEDUP_MS] {
+      frames.process_frames(&sink, &f.push("test", b"99:\n", now));
+    }
+    assert_eq!(frames.standby_count, 2);
+  }
+
+  #[test]
+  fn dedup_only_drops_the_repeated_frame() {
+    let mut f = Framer::new(FrameMode::LineText, FRAME_DEDUP_MS, LINE_IDLE_MS);
+    assert_eq!(f.push("test", b"101: 85\n", 1_000), out(&[b"101: 85"]));
+    assert_eq!(f.push("test", b"101: 85\n99:\n", 1_010), out(&[b"99:"]));
+    assert_eq!(f.push("test", b"101: 85\n", 1_020), out(&[]));
+  }
+
+  #[test]
+  fn noisy_lines_are_garbage() {
+    assert!(is_garbage("1\u{FFFD}1: 90"));
+    assert!(is_garbage(&String::from_utf8_lossy(b"1\xb51: 90")));
+    assert!(is_garbage("\x07101: 85"));
+    assert!(!is_garbage("101: 85"));
+    assert!(!is_garbage("101:\t85"));
+    assert!(!is_garbage(""));
+  }
+
+  #[test]
+  fn garbage_never_reaches_the_parser() {
+    let sink = RecordingSink::default();
+    let mut frames = FrameProcessor::new("noise-parse", CodeMapping::default(), FieldOrder::CodeFirst, SerialTuning::default());
+    frames.process_frame(&sink, &String::from_utf8_lossy(b"1\xb51: 90\n99\xff:\n"));
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert!(sink.payloads("serial-standby-ok").is_empty());
+    assert_eq!(frames.noise_run, 2);
+  }
+
+  #[test]
+  fn serial_noise_fires_at_the_threshold() {
+    let sink = RecordingSink::default();
+    let mut frames = FrameProcessor::new("noise-threshold", CodeMapping::default(), FieldOrder::CodeFirst, SerialTuning::default());
+    let noise = "\u{FFFD}\u{FFFD}:\n".repeat(NOISE_LINE_THRESHOLD as usize - 1);
+    frames.process_frame(&sink, &noise);
+    assert!(sink.payloads("serial-noise").is_empty());
+    frames.process_frame(&sink, "\u{FFFD}\u{FFFD}:\n");
+    let events = sink.payloads("serial-noise");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["port"], "noise-threshold");
+    assert_eq!(events[0]["consecutive"], NOISE_LINE_THRESHOLD);
+  }
+
+  #[test]
+  fn clean_line_resets_the_noise_run() {
+    let sink = RecordingSink::default();
+    let mut frames = FrameProcessor::new("noise-reset", CodeMapping::default(), FieldOrder::CodeFirst, SerialTuning::default());
+    let noise = "\u{FFFD}:\n".repeat(NOISE_LINE_THRESHOLD as usize - 1);
+    frames.process_frame(&sink, &noise);
+    frames.process_frame(&sink, "99:\n");
+    frames.process_frame(&sink, &noise);
+    assert!(sink.payloads("serial-noise").is_empty());
+    assert_eq!(frames.noise_run, NOISE_LINE_THRESHOLD - 1);
+  }
+
+  #[test]
+  fn threshold_direction_above_is_the_default() {
+    let v = json!({ "masterSettings": {} });
+    let (dir, threshold) = (read_threshold_direction(&v), trigger_threshold(&v));
+    assert!(dir == ThresholdDirection::Above);
+    assert!(dir.passes(85, threshold));
+    assert!(!dir.passes(20, threshold));
+  }
+
+  #[test]
+  fn below_direction_fires_low_and_rests_high() {
+    let v = site(&[("150", "Teratai", "1")], json!({ "thresholdDirection": "below", "adcThreshold": 30 }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    // at rest an active-low input sits high
+    frames.process_frame(&sink, "150: 900\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert!(history().is_empty());
+    frames.process_frame(&sink, "150: 12\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("150", "Teratai", "1")]);
+  }
+
+  #[test]
+  fn above_direction_ignores_readings_at_rest() {
+    let v = site(&[("151", "Teratai", "2")], json!({ "thresholdDirection": "above", "adcThreshold": 30 }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "151: 12\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    frames.process_frame(&sink, "151: 900\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("151", "Teratai", "2")]);
+  }
+
+  #[test]
+  fn lone_spike_is_rejected_by_hysteresis() {
+    let v = site(&[("158", "Kamboja", "1")], json!({ "triggerConsecutive": 3, "triggerWindowMs": 10_000 }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    // a reading back at rest between spikes starts the count over
+    frames.process_frame(&sink, "158: 85\n158: 85\n158: 20\n158: 85\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert!(history().is_empty());
+  }
+
+  #[test]
+  fn sustained_signal_fires_once_confirmed() {
+    let v = site(&[("159", "Kamboja", "2")], json!({ "triggerConsecutive": 3, "triggerWindowMs": 10_000 }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "159: 85\n159: 86\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    frames.process_frame(&sink, "159: 87\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("159", "Kamboja", "2")]);
+    assert_eq!(history().len(), 1);
+  }
+
+  #[test]
+  fn value_first_lines_parse_like_code_first() {
+    let mapping = CodeMapping::default();
+    let parse = |line: &str| parse_frame(line, &mapping, FieldOrder::ValueFirst);
+    assert_eq!(parse("85: 103"), Some(ParsedEvent::Trigger { code: "103".into(), adc: 85 }));
+    assert_eq!(parse("901:"), Some(ParsedEvent::Enclose { target: "101".into() }));
+    assert_eq!(parse("99:"), Some(ParsedEvent::Standby));
+    assert_eq!(parse("103: 85"), None);
+  }
+
+  #[test]
+  fn value_first_trigger_and_reset() {
+    let v = site(&[("105", "Seruni", "5")], json!({ "fieldOrder": "value-first" }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "85: 105\n");
+    frames.process_frame(&sink, "905:\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("105", "Seruni", "5"), response("105", "Seruni", "5")]);
+    assert_eq!(history()[0]["status"], "completed");
+  }
+
+  #[test]
+  fn disabled_code_records_nothing() {
+    let v = json!({
+      "masterSettings": {},
+      "masterData": [{ "charCode": "117", "roomName": "Bougenville", "bedName": "7", "enabled": false }],
+      "callHistoryStorage": []
+    });
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    processor(&v).process_frame(&sink, "117: 85\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert_eq!(sink.payloads("nurse-call-disabled"), vec![json!({ "code": "117", "adc": 85 })]);
+    assert!(history().is_empty());
+  }
+
+  #[test]
+  fn re_enabled_code_records_again() {
+    let v = json!({
+      "masterSettings": {},
+      "masterData": [{ "charCode": "118", "roomName": "Bougenville", "bedName": "8", "enabled": false }],
+      "callHistoryStorage": []
+    });
+    let _config = use_config(v.clone());
+    crate::config::set_code_enabled("118".into(), true).unwrap();
+    let sink = RecordingSink::default();
+    processor(&v).process_frame(&sink, "118: 85\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("118", "Bougenville", "8")]);
+    assert_eq!(history().len(), 1);
+  }
+
+  #[test]
+  fn blip_inside_the_grace_window_is_not_reported() {
+    let grace = disconnect_grace_ms(&json!({ "masterSettings": { "disconnectGraceMs": 2_000 } }));
+    assert_eq!(grace, 2_000);
+    // lost at t=10 s, back 800 ms later
+    assert!(!outlasted_grace(10_000, 10_800, grace));
+    // still down once the window has passed
+    assert!(outlasted_grace(10_000, 12_000, grace));
+  }
+
+  #[test]
+  fn no_grace_reports_at_once() {
+    let grace = disconnect_grace_ms(&json!({ "masterSettings": {} }));
+    assert_eq!(grace, 0);
+    assert!(outlasted_grace(10_000, 10_000, grace));
+    assert_eq!(disconnect_grace_ms(&json!({ "masterSettings": { "disconnectGraceMs": 600_000 } })), 60_000);
+  }
+
+  #[test]
+  fn leading_timestamp_does_not_shift_the_code() {
+    let mapping = CodeMapping::default();
+    let parse = |line: &str| parse_frame(line, &mapping, FieldOrder::CodeFirst);
+    assert_eq!(parse("12:34:56 103: 85"), Some(ParsedEvent::Trigger { code: "103".into(), adc: 85 }));
+    assert_eq!(parse("[12:34:56] 903:"), Some(ParsedEvent::Enclose { target: "103".into() }));
+    assert_eq!(parse("12:34:56 99:"), Some(ParsedEvent::Standby));
+  }
+
+  #[test]
+  fn baud_lookup_precedence() {
+    let last = || Some(SerialParams { baud_rate: 19200, data_bits: 7, parity: ParityKind::Even, stop_bits: 2 });
+    let explicit = SerialParams { baud_rate: 38400, ..SerialParams::default() };
+    // nothing stored: 9600-8N1
+    let p = pick_params(None, None, || None, || None);
+    assert_eq!((p.baud_rate, p.data_bits, p.stop_bits), (DEFAULT_BAUD, 8, 1));
+    // last-used params are reused as-is
+    let p = pick_params(None, None, last, || None);
+    assert_eq!((p.baud_rate, p.data_bits, p.stop_bits), (19200, 7, 2));
+    assert!(p.parity == ParityKind::Even);
+    // a known device overrides the last baud but keeps the rest of the line settings
+    let p = pick_params(None, None, last, || Some(57600));
+    assert_eq!((p.baud_rate, p.data_bits, p.stop_bits), (57600, 7, 2));
+    // explicit params win over anything stored
+    let p = pick_params(None, Some(explicit), last, || Some(57600));
+    assert_eq!((p.baud_rate, p.data_bits), (38400, 8));
+    // and an explicit baud wins over everything
+    let p = pick_params(Some(115200), Some(explicit), last, || Some(57600));
+    assert_eq!(p.baud_rate, 115200);
+    let p = pick_params(Some(115200), None, last, || Some(57600));
+    assert_eq!((p.baud_rate, p.data_bits), (115200, 7));
+  }
+
+  #[test]
+  fn disconnect_reason_follows_why_the_loop_exited() {
+    use std::io::{Error, ErrorKind};
+    // user stop: the loop ends without a read failure
+    assert_eq!(exit_reason(None), DisconnectReason::Stopped);
+    assert!(!counts_as_crash(exit_reason(None)));
+    // a read error is a fault and counts towards safe mode
+    let reason = exit_reason(Some(read_error_reason(&Error::other("framing error"))));
+    assert_eq!(reason, DisconnectReason::ReadError);
+    assert!(counts_as_crash(reason));
+    // an unplugged adapter is reported as gone
+    for kind in [ErrorKind::NotFound, ErrorKind::BrokenPipe, ErrorKind::NotConnected, ErrorKind::PermissionDenied] {
+      assert_eq!(read_error_reason(&Error::new(kind, "gone")), DisconnectReason::PortGone);
+    }
+    assert!(!counts_as_crash(DisconnectReason::TimeoutWatchdog));
+  }
+
+  #[test]
+  fn disconnect_reasons_serialize_kebab_case() {
+    let names: Vec<Value> = [DisconnectReason::Stopped, DisconnectReason::ReadError, DisconnectReason::TimeoutWatchdog, DisconnectReason::PortGone, DisconnectReason::Crashed]
+      .iter().map(|r| serde_json::to_value(r).unwrap()).collect();
+    assert_eq!(names, vec![json!("stopped"), json!("read-error"), json!("timeout-watchdog"), json!("port-gone"), json!("crashed")]);
+  }
+
+  #[test]
+  fn standby_flood_does_not_complete_the_next_call_early() {
+    let v = site(&[("113", "Dahlia", "3")], json!({ "standbyResetPulses": 3 }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    // nothing awaits reset, so a flood of standbys is not counted
+    for _ in 0..10 { frames.process_frame(&sink, "99:\n"); }
+    assert_eq!(frames.standby_count, 0);
+    frames.process_frame(&sink, "113: 85\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("113", "Dahlia", "3")]);
+    for _ in 0..2 { frames.process_frame(&sink, "99:\n"); }
+    assert_eq!(frames.standby_count, 2);
+    assert_ne!(history()[0]["status"], "completed");
+    frames.process_frame(&sink, "99:\n");
+    assert_eq!(history()[0]["status"], "completed");
+    assert!(!frames.awaiting_reset);
+  }
+
+  #[test]
+  fn weak_reset_is_ignored_by_default() {
+    let v = site(&[("106", "Kenanga", "1")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "106: 85\n");
+    frames.process_frame(&sink, "906: 10\n");
+    assert_ne!(history()[0]["status"], "completed");
+  }
+
+  #[test]
+  fn weak_reset_completes_when_threshold_not_required() {
+    let v = site(&[("107", "Kenanga", "2")], json!({ "resetRequiresThreshold": false }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "107: 85\n");
+    frames.process_frame(&sink, "907: 10\n");
+    assert_eq!(history()[0]["status"], "completed");
+  }
+
+  #[test]
+  fn epoch_fields_agree_with_iso_fields() {
+    let v = site(&[("108", "Kenanga", "3")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "108: 85\n");
+    frames.process_frame(&sink, "908:\n");
+    let rec = &history()[0];
+    for (iso_key, ms_key) in [("timestamp", "timestampMs"), ("resetTime", "resetTimeMs")] {
+      let iso = chrono::DateTime::parse_from_rfc3339(rec[iso_key].as_str().unwrap()).unwrap();
+      assert_eq!(rec[ms_key].as_i64().unwrap(), iso.timestamp_millis(), "{}", ms_key);
+    }
+  }
+
+  #[test]
+  fn aliased_codes_make_one_active_call() {
+    let v = site(&[("109", "Cempaka", "1")], json!({ "codeAliases": { "119": "109" } }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "109: 85\n");
+    frames.process_frame(&sink, "119: 85\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("109", "Cempaka", "1")]);
+    let recs = history();
+    assert_eq!(recs.len(), 1);
+    assert_eq!(recs[0]["code"], "109");
+  }
+
+  #[test]
+  fn aliased_codes_stay_separate_without_alias_dedup() {
+    let v = site(&[("114", "Cempaka", "2")], json!({ "codeAliases": { "116": "114" }, "aliasDedup": false }), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "114: 85\n");
+    frames.process_frame(&sink, "116: 85\n");
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("114", "Cempaka", "2"), nurse_call("116", "Cempaka", "2")]);
+    assert_eq!(history().len(), 2);
+  }
+
+  #[test]
+  fn frame_split_across_reads_fires_one_call() {
+    let v = site(&[("121", "Teratai", "1")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    let mut f = framer(FrameMode::LineText);
+    for (now, chunk) in [(1_000, &b"12"[..]), (1_005, &b"1: 8"[..]), (1_010, &b"5\r\n"[..])] {
+      frames.process_frames(&sink, &f.push("test", chunk, now));
+    }
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("121", "Teratai", "1")]);
+    assert_eq!(history().len(), 1);
+  }
+
+  #[test]
+  fn parse_frame_table() {
+    let mapping = CodeMapping::default();
+    let trigger = |code: &str, adc| Some(ParsedEvent::Trigger { code: code.into(), adc });
+    for (line, want) in [
+      ("101: 85", trigger("101", 85)),
+      ("101:85", trigger("101", 85)),
+      ("  101: 85 extra", trigger("101", 85)),
+      // a reset code with a reading goes through the trigger path (threshold applies)
+      ("901: 85", trigger("901", 85)),
+      ("901:", Some(ParsedEvent::Enclose { target: "101".into() })),
+      ("99:", Some(ParsedEvent::Standby)),
+      ("99: 12", Some(ParsedEvent::Standby)),
+      // a missing reading counts as 0 and fails any threshold downstream
+      ("101:", trigger("101", 0)),
+      // malformed lines
+      ("", None),
+      ("101 85", None),
+      ("1011: 85", None),
+      ("10a: 85", None),
+      // non-numeric ADC
+      ("101: high", None),
+      ("101: 8x5", None),
+      ("101: -5", None),
+    ] {
+      assert_eq!(parse_frame(line, &mapping, FieldOrder::CodeFirst), want, "{:?}", line);
+    }
+  }
+
+  #[test]
+  fn should_emit_keys_are_independent() {
+    assert!(should_emit("test-dedup:enclose:103", 60_000));
+    // a different key inside the window is still allowed
+    assert!(should_emit("test-dedup:trigger:105", 60_000));
+    // the same key is held back
+    assert!(!should_emit("test-dedup:enclose:103", 60_000));
+    assert!(!should_emit("test-dedup:trigger:105", 60_000));
+  }
+
+  #[test]
+  fn diagnosed_trigger_leaves_history_unchanged() {
+    let v = site(&[("123", "Teratai", "3")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    *DIAGNOSE.lock().unwrap_or_else(|e| e.into_inner()) = Some(DiagnoseSession { code: "123".into(), threshold: 50, direction: ThresholdDirection::Above, hits: Vec::new() });
+    let guard = DiagnoseGuard;
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    frames.process_frame(&sink, "123: 85\n");
+    assert!(sink.only(&CALL_EVENTS).is_empty());
+    assert_eq!(sink.payloads("serial-diagnose").len(), 1);
+    assert!(history().is_empty());
+    assert!(!frames.awaiting_reset);
+    let hits = guard.finish();
+    assert_eq!((hits.len(), hits[0].adc, hits[0].passes), (1, 85, true));
+  }
+
+  #[test]
+  fn split_wider_than_a_read_timeout_is_joined() {
+    let v = site(&[("124", "Teratai", "4")], json!({}), json!([]));
+    let _config = use_config(v.clone());
+    let sink = RecordingSink::default();
+    let mut frames = processor(&v);
+    let mut f = framer(FrameMode::LineText);
+    frames.process_frames(&sink, &f.push("test", b"12", 1_000));
+    // the read times out (and again) before the rest arrives
+    frames.process_frames(&sink, &f.flush_idle(1_000 + READ_TIMEOUT_MS as u128));
+    frames.process_frames(&sink, &f.flush_idle(1_000 + 2 * READ_TIMEOUT_MS as u128));
+    frames.process_frames(&sink, &f.push("test", b"4: 85\n", 1_450));
+    assert_eq!(sink.only(&CALL_EVENTS), vec![nurse_call("124", "Teratai", "4")]);
+    assert_eq!(history().len(), 1);
+  }
+
+  #[test]
+  fn line_idle_is_never_shorter_than_a_read_timeout() {
+    let idle = |n: u64| read_tuning(&json!({ "masterSettings": { "lineIdleMs": n } })).line_idle_ms;
+    assert_eq!(read_tuning(&json!({})).line_idle_ms, LINE_IDLE_MS);
+    assert_eq!(idle(1_500), 1_500);
+    assert!(idle(0) > READ_TIMEOUT_MS as u128);
+  }
+
+  #[test]
+  fn replay_flag_is_cleared_after_a_panic() {
+    REPLAYING.store(true, Ordering::SeqCst);
+    let panicked = std::panic::catch_unwind(|| {
+      let _guard = ReplayGuard;
+      panic!("frame processing failed");
+    });
+    assert!(panicked.is_err());
+    assert!(!REPLAYING.load(Ordering::SeqCst));
+  }
 }
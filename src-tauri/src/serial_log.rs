@@ -0,0 +1,131 @@
+use chrono::{Local, NaiveTime, Timelike};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::{fs, io::Write, path::{Path, PathBuf}, sync::Mutex};
+
+use crate::config::{config_path, lock_config, read_config, write_config};
+
+// total size of serial-logs/ before the oldest days are deleted
+const MAX_LOG_BYTES: u64 = 50 * 1024 * 1024;
+// chunks beyond this between flushes are dropped rather than growing without bound
+const MAX_PENDING_BYTES: usize = 1024 * 1024;
+
+struct RawLog {
+  enabled: bool,
+  pending: String,
+  dropped: usize,
+}
+
+static RAW_LOG: Lazy<Mutex<RawLog>> = Lazy::new(|| Mutex::new(RawLog { enabled: false, pending: String::new(), dropped: 0 }));
+
+fn log_dir() -> PathBuf {
+  let cfg_path = config_path();
+  Path::new(&cfg_path).parent().unwrap_or(Path::new(".")).join("serial-logs")
+}
+
+fn read_enabled(v: &Value) -> bool {
+  v.get("masterSettings").and_then(|m| m.get("serialLogging")).and_then(|b| b.as_bool()).unwrap_or(false)
+}
+
+/// Read loop: queues a received chunk with a timestamp. Only touches memory, so it never
+/// stalls reading; `flush` writes it out. Bytes are escaped so CR/LF and noise stay visible.
+pub(crate) fn record(port: &str, bytes: &[u8]) {
+  let mut log = RAW_LOG.lock().unwrap_or_else(|e| e.into_inner());
+  if !log.enabled { return; }
+  if log.pending.len() > MAX_PENDING_BYTES { log.dropped += bytes.len(); return; }
+  let line = format!("{} {} {}\n", Local::now().format("%H:%M:%S%.3f"), port, bytes.escape_ascii());
+  log.pending.push_str(&line);
+}
+
+/// Called from `long_running_thread`: follows `masterSettings.serialLogging` and appends queued
+/// chunks to `serial-logs/<YYYY-MM-DD>.log` next to config.json.
+pub(crate) fn flush(v: &Value) {
+  let (text, dropped) = {
+    let mut log = RAW_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.enabled = read_enabled(v);
+    if !log.enabled { log.pending.clear(); }
+    (std::mem::take(&mut log.pending), std::mem::take(&mut log.dropped))
+  };
+  if dropped > 0 { log::warn!("serial log dropped {} bytes", dropped); }
+  if text.is_empty() { return; }
+  let dir = log_dir();
+  let path = dir.join(format!("{}.log", Local::now().format("%Y-%m-%d")));
+  let res = fs::create_dir_all(&dir)
+    .and_then(|_| fs::OpenOptions::new().create(true).append(true).open(&path))
+    .and_then(|mut f| f.write_all(text.as_bytes()));
+  if let Err(e) = res { log::warn!("serial log write failed ({}): {}", path.display(), e); return; }
+  prune(&dir, &path);
+}
+
+// deletes the oldest day files until the directory fits MAX_LOG_BYTES; today's file is kept
+fn prune(dir: &Path, current: &Path) {
+  let Ok(entries) = fs::read_dir(dir) else { return };
+  let mut files: Vec<(PathBuf, u64)> = entries
+    .filter_map(|e| e.ok())
+    .map(|e| e.path())
+    .filter(|p| p.extension().and_then(|x| x.to_str()) == Some("log"))
+    .filter_map(|p| fs::metadata(&p).ok().map(|m| (p, m.len())))
+    .collect();
+  // names are dates, so name order is age order
+  files.sort();
+  let mut total: u64 = files.iter().map(|(_, len)| len).sum();
+  for (path, len) in files {
+    if total <= MAX_LOG_BYTES { break; }
+    if path == current { continue; }
+    if fs::remove_file(&path).is_ok() { total = total.saturating_sub(len); }
+  }
+}
+
+// reverses `escape_ascii`: \n \r \t \' \" \\ and \xNN, everything else as is
+fn unescape(text: &str) -> Option<Vec<u8>> {
+  let bytes = text.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] != b'\\' { out.push(bytes[i]); i += 1; continue; }
+    let (byte, used) = match *bytes.get(i + 1)? {
+      b'n' => (b'\n', 2),
+      b'r' => (b'\r', 2),
+      b't' => (b'\t', 2),
+      b'x' => (u8::from_str_radix(text.get(i + 2..i + 4)?, 16).ok()?, 4),
+      other => (other, 2),
+    };
+    out.push(byte);
+    i += used;
+  }
+  Some(out)
+}
+
+/// Reads a file written by `flush` back as (milliseconds since midnight, received bytes),
+/// in file order. Lines that don't parse are skipped.
+pub(crate) fn read_chunks(path: &str) -> Result<Vec<(u32, Vec<u8>)>, String> {
+  let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+  let chunks: Vec<(u32, Vec<u8>)> = text
+    .lines()
+    .filter_map(|line| {
+      // "<time> <port> <escaped bytes>"; the bytes may contain spaces, the port doesn't
+      let mut parts = line.splitn(3, ' ');
+      let at = NaiveTime::parse_from_str(parts.next()?, "%H:%M:%S%.3f").ok()?;
+      let _port = parts.next()?;
+      let bytes = unescape(parts.next()?)?;
+      Some((at.num_seconds_from_midnight() * 1000 + at.nanosecond() / 1_000_000, bytes))
+    })
+    .collect();
+  if chunks.is_empty() { return Err(format!("{}: no serial log lines found", path)); }
+  Ok(chunks)
+}
+
+/// Turns raw serial logging on or off now and saves it as `masterSettings.serialLogging`.
+/// Off by default; when on, every received chunk is appended to `serial-logs/<date>.log`
+/// (capped at 50 MB, oldest days deleted first).
+#[tauri::command]
+pub fn serial_logging_set(enabled: bool) -> Result<(), String> {
+  let _guard = lock_config();
+  let mut v = read_config()?;
+  let obj = v.as_object_mut().ok_or("config.json is not an object")?;
+  let settings = obj.entry("masterSettings").or_insert_with(|| json!({}));
+  settings.as_object_mut().ok_or("masterSettings is not an object")?.insert("serialLogging".into(), Value::Bool(enabled));
+  write_config(&v)?;
+  RAW_LOG.lock().unwrap_or_else(|e| e.into_inner()).enabled = enabled;
+  Ok(())
+}
@@ -1,8 +1,15 @@
+use once_cell::sync::Lazy;
 use serde::Serialize;
+use serde_json::json;
 use std::sync::Mutex;
 use tauri::menu::{Menu, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
 use tauri::{self, command, Emitter, Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use crate::autostart::APP_STORE;
+use crate::serial::{is_open, record_priority, Priority};
+use crate::utils::{emit_event, EventScope};
 
 #[derive(Clone, Serialize)]
 pub struct IconTrayPayload {
@@ -17,22 +24,68 @@ impl IconTrayPayload {
   }
 }
 
+#[derive(PartialEq)]
 pub enum TrayState {
   NotPlaying,
   Playing,
+  // nurse calls waiting for a response and the most urgent of them; shows the alert icon
+  Active(u32, Priority),
+}
+
+const TRAY_TOOLTIP: &str = "NCRS";
+// matches defaultLng in src/translations/i18n.ts
+const DEFAULT_LANG: &str = "id";
+
+// language of the tray menu and tooltip; set from the store at startup and by tray_update_lang
+static TRAY_LANG: Lazy<Mutex<&'static str>> = Lazy::new(|| Mutex::new(DEFAULT_LANG));
+
+// "en-US" -> "en"; anything without tray strings falls back to the app default
+fn supported_lang(lang: &str) -> &'static str {
+  match lang.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase().as_str() {
+    "en" => "en",
+    _ => DEFAULT_LANG,
+  }
+}
+
+fn current_lang() -> &'static str { *TRAY_LANG.lock().unwrap_or_else(|e| e.into_inner()) }
+
+// Tray labels only; room/bed names in call data are never translated.
+fn tr(lang: &str, key: &str) -> &'static str {
+  match (lang, key) {
+    ("en", "hide-window") => "Hide Window",
+    ("en", "quit") => "Quit",
+    ("en", "toggle-tray-icon") => "Toggle the tray icon",
+    ("en", "active-call") => "active call",
+    ("en", "active-calls") => "active calls",
+    ("en", "low") => "low",
+    ("en", "high") => "high",
+    ("en", "emergency") => "emergency",
+    (_, "hide-window") => "Sembunyikan Jendela",
+    (_, "quit") => "Keluar",
+    (_, "toggle-tray-icon") => "Ganti ikon tray",
+    (_, "active-call" | "active-calls") => "panggilan aktif",
+    (_, "low") => "rendah",
+    (_, "high") => "tinggi",
+    (_, "emergency") => "darurat",
+    _ => "",
+  }
+}
+
+// Tooltip for the current state in the current language.
+fn tooltip_for(state: &TrayState) -> String {
+  let lang = current_lang();
+  let TrayState::Active(count, top) = state else { return TRAY_TOOLTIP.to_string() };
+  let mut tooltip = format!("{} - {} {}", TRAY_TOOLTIP, count, tr(lang, if *count == 1 { "active-call" } else { "active-calls" }));
+  if *top > Priority::Normal { tooltip.push_str(&format!(" ({})", tr(lang, top.as_str()))); }
+  tooltip
 }
 
 // https://v2.tauri.app/start/migrate/from-tauri-1/#migrate-to-menu-module
 pub fn create_tray_menu<R: Runtime>(
   app: &tauri::AppHandle<R>,
-  _lang: String,
+  lang: &str,
 ) -> Result<Menu<R>, tauri::Error> {
-  // TODO: tray internationalization https://docs.rs/rust-i18n/latest/rust_i18n/
-  // untested, not sure if the macro accepts dynamic languages
-  // ENTER rust_i18n::set_locale(lang) IF LOCAL=lang DOES NOT COMPILE
-  // .add_item("id".to_string(), t!("Label", locale = lang))
-  // .add_item("id".to_string(), t!("Label")
-  let toggle = MenuItemBuilder::with_id("toggle-visibility", "Hide Window")
+  let toggle = MenuItemBuilder::with_id("toggle-visibility", tr(lang, "hide-window"))
     .accelerator("Ctrl+Shift+T")
     .build(app)?;
   MenuBuilder::new(app)
@@ -45,51 +98,48 @@ pub fn create_tray_menu<R: Runtime>(
         .text("af-sep", "After Separator")
         .build()?,
       &toggle,
-      &MenuItemBuilder::with_id("quit", "Quit")
+      &MenuItemBuilder::with_id("quit", tr(lang, "quit"))
         .accelerator("Ctrl+Q")
         .build(app)?,
-      &MenuItemBuilder::with_id("toggle-tray-icon", "Toggle the tray icon").build(app)?,
+      &MenuItemBuilder::with_id("toggle-tray-icon", tr(lang, "toggle-tray-icon")).build(app)?,
     ])
     .build()
 }
 
 static TRAY_ID: &'static str = "tray-main";
 
+/// Builds the tray in the language saved by the last tray_update_lang.
 pub fn create_tray_icon(app: &tauri::AppHandle) -> Result<TrayIcon, tauri::Error> {
+  if let Some(saved) = app.store(APP_STORE).ok().and_then(|s| s.get("language")).and_then(|v| v.as_str().map(supported_lang)) {
+    *TRAY_LANG.lock().unwrap_or_else(|e| e.into_inner()) = saved;
+  }
   TrayIconBuilder::with_id(TRAY_ID)
-    .menu(&create_tray_menu(app, "en".into())?)
+    .menu(&create_tray_menu(app, current_lang())?)
     .show_menu_on_left_click(true)
     .on_menu_event(move |app, event| {
       if let Some(main_window) = app.get_webview_window("main") {
         let _ = main_window.emit("systemTray", IconTrayPayload::new(&event.id().as_ref()));
       }
-      let tray_icon = app.tray_by_id(TRAY_ID).unwrap();
+      let Some(tray_icon) = app.tray_by_id(TRAY_ID) else { return };
 
       // TODO: FIGURE OUT HOW TO GET THE ITEM HANDLER IN v2
       // let item_handle: MenuItem = tray_icon.get_item();
 
       match event.id().as_ref() {
         "quit" => {
-          std::process::exit(0);
+          // through the run loop so the exit hook can flush pending writes
+          app.exit(0);
         }
         "toggle-tray-icon" => {
           let tray_state_mutex = app.state::<Mutex<TrayState>>();
-          let mut tray_state = tray_state_mutex.lock().unwrap();
+          let mut tray_state = tray_state_mutex.lock().unwrap_or_else(|e| e.into_inner());
           match *tray_state {
             TrayState::NotPlaying => {
-              tray_icon
-                .set_icon(
-                  tauri::image::Image::from_bytes(include_bytes!("../icons/SystemTray2.ico")).ok(),
-                )
-                .unwrap();
+              let _ = tray_icon.set_icon(tray_image(true));
               *tray_state = TrayState::Playing;
             }
-            TrayState::Playing => {
-              tray_icon
-                .set_icon(
-                  tauri::image::Image::from_bytes(include_bytes!("../icons/SystemTray1.ico")).ok(),
-                )
-                .unwrap();
+            TrayState::Playing | TrayState::Active(..) => {
+              let _ = tray_icon.set_icon(tray_image(false));
               *tray_state = TrayState::NotPlaying;
             }
           };
@@ -98,11 +148,11 @@ pub fn create_tray_icon(app: &tauri::AppHandle) -> Result<TrayIcon, tauri::Error
           if let Some(main_window) = app.get_webview_window("main") {
             // update menu item example (TODO: support tauri v2)
             // proposed implementation: update entire menu
-            if main_window.is_visible().unwrap() {
-              main_window.hide().unwrap();
+            if main_window.is_visible().unwrap_or(false) {
+              let _ = main_window.hide();
               // item_handle.set_title("Show Window").unwrap();
             } else {
-              main_window.show().unwrap();
+              let _ = main_window.show();
               // item_handle.set_title("Hide Window").unwrap();
             }
           }
@@ -138,11 +188,67 @@ pub fn create_tray_icon(app: &tauri::AppHandle) -> Result<TrayIcon, tauri::Error
     .build(app)
 }
 
+fn tray_image(alert: bool) -> Option<tauri::image::Image<'static>> {
+  let bytes: &'static [u8] = if alert {
+    include_bytes!("../icons/SystemTray2.ico")
+  } else {
+    include_bytes!("../icons/SystemTray1.ico")
+  };
+  tauri::image::Image::from_bytes(bytes).ok()
+}
+
+/// Shows the pending call count in the tooltip and swaps to the alert icon while it is above
+/// zero; `top` is the highest priority among them and is named when above normal. The managed
+/// `TrayState` decides whether anything changed.
+pub fn tray_set_active_count(app: &tauri::AppHandle, count: u32, top: Priority) {
+  let Some(state) = app.try_state::<Mutex<TrayState>>() else { return };
+  let mut tray_state = state.lock().unwrap_or_else(|e| e.into_inner());
+  let next = if count > 0 { TrayState::Active(count, top) } else { TrayState::NotPlaying };
+  // zero calls only resets an alert this function set, not a manual toggle
+  if *tray_state == next || (count == 0 && !matches!(*tray_state, TrayState::Active(..))) {
+    return;
+  }
+  if let Some(tray) = app.tray_by_id(TRAY_ID) {
+    let _ = tray.set_icon(tray_image(count > 0));
+    let _ = tray.set_tooltip(Some(tooltip_for(&next)));
+  }
+  *tray_state = next;
+}
+
+/// Called from `long_running_thread`: recomputes the open call count and their highest
+/// priority from callHistoryStorage.
+pub(crate) fn sync_active_count(app: &tauri::AppHandle, v: &serde_json::Value) {
+  let (count, top) = v
+    .get("callHistoryStorage")
+    .and_then(|a| a.as_array())
+    .map(|arr| {
+      arr
+        .iter()
+        .filter(|rec| is_open(rec))
+        .fold((0, Priority::Low), |(n, top), rec| (n + 1, top.max(record_priority(v, rec))))
+    })
+    .unwrap_or((0, Priority::Low));
+  tray_set_active_count(app, count, top);
+}
+
+/// Switches the tray menu and tooltip to `lang` (e.g. "id", "en-US"; unsupported falls back to
+/// Indonesian), saves it for the next start and emits `lang-changed` so windows can re-fetch.
 #[command]
 #[allow(unused_must_use)]
 pub fn tray_update_lang(app: tauri::AppHandle, lang: String) {
+  let lang = supported_lang(&lang);
+  *TRAY_LANG.lock().unwrap_or_else(|e| e.into_inner()) = lang;
+  if let Ok(store) = app.store(APP_STORE) {
+    store.set("language", json!(lang));
+    store.save();
+  }
   let tray_handle = app.tray_by_id(TRAY_ID);
   if let Some(t) = tray_handle {
     t.set_menu(create_tray_menu(&app, lang).ok());
+    // re-render the tooltip for whatever the tray currently shows
+    if let Some(state) = app.try_state::<Mutex<TrayState>>() {
+      t.set_tooltip(Some(tooltip_for(&state.lock().unwrap_or_else(|e| e.into_inner()))));
+    }
   }
+  emit_event(&app, EventScope::Status, "lang-changed", &json!({ "lang": lang }));
 }
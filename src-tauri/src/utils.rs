@@ -1,8 +1,165 @@
+use once_cell::sync::Lazy;
 use serde::Serialize;
-use std::time::Duration;
+use serde_json::Value;
+use std::{collections::{HashMap, VecDeque}, sync::Mutex, time::Duration};
 // State is used by linux
 use tauri::{Emitter, Manager};
 
+/// Routing class of a backend event. `Call` and `Diagnostic` events can be pointed at specific
+/// window labels through `masterSettings.emitTargets`; `Status` events always broadcast.
+#[derive(Clone, Copy)]
+pub(crate) enum EventScope {
+  Call,
+  Diagnostic,
+  Status,
+}
+
+#[derive(Default)]
+struct EmitTargets {
+  call: Option<Vec<String>>,
+  diagnostic: Option<Vec<String>>,
+}
+
+// refreshed from config by long_running_thread; None means broadcast
+static EMIT_TARGETS: Lazy<Mutex<EmitTargets>> = Lazy::new(|| Mutex::new(EmitTargets::default()));
+
+fn read_labels(targets: Option<&Value>, key: &str) -> Option<Vec<String>> {
+  let labels: Vec<String> = targets?.get(key)?.as_array()?
+    .iter()
+    .filter_map(|s| s.as_str().map(|s| s.to_string()))
+    .collect();
+  if labels.is_empty() { None } else { Some(labels) }
+}
+
+pub(crate) fn refresh_emit_targets(v: &Value) {
+  let targets = v.get("masterSettings").and_then(|m| m.get("emitTargets"));
+  *EMIT_TARGETS.lock().unwrap_or_else(|e| e.into_inner()) = EmitTargets {
+    call: read_labels(targets, "call"),
+    diagnostic: read_labels(targets, "diagnostic"),
+  };
+}
+
+/// Where `emit_event` delivers to. The app handle is the real sink; the frame/trigger/enclose
+/// path only needs this, so it can run against anything that records what was sent.
+pub(crate) trait EventSink {
+  /// `label` is a window label, or None to broadcast.
+  fn send<S: Serialize + Clone>(&self, label: Option<&str>, event: &str, payload: S);
+}
+
+impl EventSink for tauri::AppHandle {
+  fn send<S: Serialize + Clone>(&self, label: Option<&str>, event: &str, payload: S) {
+    let _ = match label {
+      Some(label) => self.emit_to(label, event, payload),
+      None => self.emit(event, payload),
+    };
+  }
+}
+
+/// Test sink: keeps every delivered event as (name, payload as JSON), in order.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct RecordingSink(std::cell::RefCell<Vec<(String, Value)>>);
+
+#[cfg(test)]
+impl RecordingSink {
+  /// The recorded events named in `events`, in the order they were sent.
+  pub(crate) fn only(&self, events: &[&str]) -> Vec<(String, Value)> {
+    self.0.borrow().iter().filter(|(e, _)| events.contains(&e.as_str())).cloned().collect()
+  }
+
+  pub(crate) fn payloads(&self, event: &str) -> Vec<Value> {
+    self.0.borrow().iter().filter(|(e, _)| e == event).map(|(_, p)| p.clone()).collect()
+  }
+}
+
+#[cfg(test)]
+impl EventSink for RecordingSink {
+  fn send<S: Serialize + Clone>(&self, _label: Option<&str>, event: &str, payload: S) {
+    self.0.borrow_mut().push((event.to_string(), serde_json::to_value(payload).unwrap_or(Value::Null)));
+  }
+}
+
+// events a reloading window must not miss; kept per window until it says it is listening
+const CRITICAL_EVENTS: [&str; 3] = ["nurse-call", "nurse-call-muted", "nurse-call-response"];
+const MAX_UNDELIVERED: usize = 100;
+
+struct Undelivered {
+  // windows between a page (re)load and their frontend-ready, with what they missed meanwhile
+  loading: HashMap<String, VecDeque<(String, Value)>>,
+  // events from before any window started loading; seeds each window's first load and is
+  // dropped once a window is ready
+  startup: Option<VecDeque<(String, Value)>>,
+}
+
+static UNDELIVERED: Lazy<Mutex<Undelivered>> = Lazy::new(|| Mutex::new(Undelivered { loading: HashMap::new(), startup: Some(VecDeque::new()) }));
+
+fn queue_undelivered(queue: &mut VecDeque<(String, Value)>, event: &str, payload: Value) {
+  if queue.len() >= MAX_UNDELIVERED {
+    // responses go first: their call is already closed in callHistoryStorage
+    let oldest = queue.iter().position(|(e, _)| e == "nurse-call-response").unwrap_or(0);
+    queue.remove(oldest);
+  }
+  queue.push_back((event.to_string(), payload));
+}
+
+/// Page-load hook: window `label` is (re)loading, so its critical events are queued until it is ready.
+pub(crate) fn frontend_unloaded(label: &str) {
+  let mut pending = UNDELIVERED.lock().unwrap_or_else(|e| e.into_inner());
+  let startup = pending.startup.clone().unwrap_or_default();
+  // a reload before the last one finished keeps what that one was still owed
+  pending.loading.entry(label.to_string()).or_insert(startup);
+}
+
+/// `frontend-ready` listener: re-delivers the events window `label` missed while loading, oldest
+/// first and tagged `"queued": true`, to that window only (the others and hallway displays
+/// already got them).
+pub(crate) fn frontend_ready<E: EventSink>(app: &E, label: &str) {
+  let queued = {
+    let mut pending = UNDELIVERED.lock().unwrap_or_else(|e| e.into_inner());
+    pending.startup = None;
+    pending.loading.remove(label).unwrap_or_default()
+  };
+  for (event, mut payload) in queued {
+    if let Some(obj) = payload.as_object_mut() { obj.insert("queued".into(), Value::Bool(true)); }
+    app.send(Some(label), &event, payload);
+  }
+}
+
+/// Single path for backend events: broadcasts unless the scope has configured window labels.
+pub(crate) fn emit_event<E: EventSink, S: Serialize + Clone>(app: &E, scope: EventScope, event: &str, payload: S) {
+  // hallway displays get nurse calls regardless of window targeting
+  crate::broadcast::publish(event, &payload);
+  if CRITICAL_EVENTS.contains(&event) {
+    if let Ok(value) = serde_json::to_value(&payload) {
+      let labels = target_labels(scope);
+      let mut pending = UNDELIVERED.lock().unwrap_or_else(|e| e.into_inner());
+      let Undelivered { loading, startup } = &mut *pending;
+      for (label, queue) in loading.iter_mut() {
+        if labels.as_ref().is_none_or(|l| l.contains(label)) { queue_undelivered(queue, event, value.clone()); }
+      }
+      if let Some(queue) = startup { queue_undelivered(queue, event, value); }
+    }
+  }
+  deliver(app, scope, event, payload);
+}
+
+fn target_labels(scope: EventScope) -> Option<Vec<String>> {
+  let targets = EMIT_TARGETS.lock().unwrap_or_else(|e| e.into_inner());
+  match scope {
+    EventScope::Call => targets.call.clone(),
+    EventScope::Diagnostic => targets.diagnostic.clone(),
+    EventScope::Status => None,
+  }
+}
+
+fn deliver<E: EventSink, S: Serialize + Clone>(app: &E, scope: EventScope, event: &str, payload: S) {
+  let labels = target_labels(scope);
+  match labels {
+    Some(labels) => for label in labels { app.send(Some(label.as_str()), event, payload.clone()); },
+    None => app.send(None, event, payload),
+  }
+}
+
 #[derive(Clone, Serialize)]
 struct LongRunningThreadStruct {
   message: String,
@@ -21,5 +178,18 @@ pub async fn long_running_thread(app: &tauri::AppHandle) {
       )
       .ok()
     });
+    if let Ok(v) = crate::config::read_config() {
+      refresh_emit_targets(&v);
+      crate::history::repeat_active_calls(app, &v);
+      crate::config::warn_if_large(app, &v);
+      crate::config::report_normalized(app);
+      crate::history::track_oldest_active(app, &v);
+      crate::history::expire_snooze(app, &v);
+      crate::history::tick_active_calls(app, &v);
+      crate::history::escalate_unanswered(app, &v);
+      crate::tray_icon::sync_active_count(app, &v);
+      crate::serial_log::flush(&v);
+      crate::call_log::refresh(&v);
+    }
   }
 }
@@ -20,7 +20,7 @@ import 'simplebar-react/dist/simplebar.min.css';
 import classes from './App.module.css';
 import { useCookie, notify as osNotify } from './common/utils';
 import { sendTelegram, buildTriggerMessage, buildResponseMessage } from './lib/notify/telegram';
-import { audioQueue } from './lib/audio/queue';
+import { audioQueue, listAudioOutputs } from './lib/audio/queue';
 import dayjs from 'dayjs';
 import LanguageHeaders from './components/LanguageHeaders';
 import { ScrollToTop } from './components/ScrollToTop';
@@ -44,7 +44,7 @@ interface View {
 }
 
 export default function () {
-	const { t } = useTranslation();
+	const { t, i18n } = useTranslation();
 	// check if using custom titlebar to adjust other components
 	const { usingCustomTitleBar } = useTauriContext();
 
@@ -190,13 +190,10 @@ export default function () {
         if (!isTauri()) return; // only supported in desktop env
         const check = async () => {
             try {
-                const cfg = await fetch('/config.json?ts=' + Date.now());
+                const json: any = await invoke('config_read');
                 let needed = new Set<string>();
-                if (cfg.ok) {
-                    const json = await cfg.json();
-                    const md: any[] = json?.masterData ?? [];
-                    md.forEach(r => ['v1','v2','v3','v4','v5','v6'].forEach(k => { const v=r?.[k]; if (v && v !== '-') needed.add(String(v)); }));
-                }
+                const md: any[] = json?.masterData ?? [];
+                md.forEach(r => ['v1','v2','v3','v4','v5','v6'].forEach(k => { const v=r?.[k]; if (v && v !== '-') needed.add(String(v)); }));
                 // verify availability via HTTP requests to /sounds/<file>
                 const names = Array.from(needed);
                 let presentCount = 0;
@@ -257,6 +254,14 @@ export default function () {
 			return () => { try { unlisten?.(); } catch {} };
 		}, []);
 
+		// keep the tray menu/tooltip in the UI language (the backend saves it for the next start)
+		useEffect(() => {
+			const sync = (lng: string) => { invoke('tray_update_lang', { lang: lng }).catch(console.error); };
+			sync(i18n.resolvedLanguage ?? i18n.language);
+			i18n.on('languageChanged', sync);
+			return () => { i18n.off('languageChanged', sync); };
+		}, []);
+
 		// update checker
 		useEffect(() => {
 			(async () => {
@@ -321,9 +326,10 @@ export default function () {
         useEffect(() => {
             (async () => {
                 try {
-                    const res = await fetch('/config.json?ts=' + Date.now());
-                    if (!res.ok) return;
-                    const json = await res.json();
+                    // the backend may already have reconnected to the last port on startup
+                    const status: any = await invoke('serial_status');
+                    if (status?.port) { if (status.connected) setStandbyStatus('green'); return; }
+                    const json: any = await invoke('config_read');
                     const saved = json?.masterSettings?.com;
                     if (!saved) return;
                     const ports: string[] = await invoke('serial_list_ports');
@@ -338,24 +344,49 @@ export default function () {
         const serialConnectedRef = useRef(false);
         useEffect(() => { serialConnectedRef.current = standbyStatus !== 'red'; }, [standbyStatus]);
 
+        // alert output device: report what the webview can play to, apply masterSettings.audioOutput
+        // and follow set_audio_output; a saved device that is gone plays to the default instead
+        useEffect(() => {
+            if (!isTauri()) return;
+            const apply = (name: string | null) => audioQueue.setOutput(name).catch(e => console.warn('audio output', e));
+            const report = async () => {
+                try { await tauriEvent.emit('audio-outputs', { names: (await listAudioOutputs()).map(d => d.label).filter(Boolean) }); } catch (e) { console.error('audio-outputs', e); }
+            };
+            void report();
+            navigator.mediaDevices?.addEventListener?.('devicechange', report);
+            invoke<any>('config_read').then(cfg => apply(cfg?.masterSettings?.audioOutput || null)).catch(console.error);
+            let unlisten: any;
+            listen<{ name: string | null }>('audio-output-changed', (e) => { void apply(e.payload?.name || null); }).then(u => unlisten = u);
+            return () => { navigator.mediaDevices?.removeEventListener?.('devicechange', report); if (unlisten) unlisten(); };
+        }, []);
+
         // nurse-call sound playback (trigger) + response app notification (de-duped)
         useEffect(() => {
             audioQueue.init();
             let unlisten: any;
-            listen<{ code: string, files: string[], display?: string, room?: string, bed?: string }>('nurse-call', (e) => {
-                // Ignore triggers while disconnected
-                if (!serialConnectedRef.current) return;
+            listen<{ code: string, files: string[], display?: string, room?: string, bed?: string, snoozed?: boolean, realarm?: boolean, training?: boolean, queued?: boolean }>('nurse-call', (e) => {
+                // Ignore triggers while disconnected; a queued call was recorded by the backend while
+                // this page was loading, so it is real even if the status check hasn't finished
+                if (!serialConnectedRef.current && !e.payload?.queued) return;
                 const files = (e.payload?.files || []).filter(Boolean);
                 const code = String(e.payload?.code || (e.payload?.display || ''));
+                // training replays are never recorded, so they skip the active/closed bookkeeping
+                const training = !!e.payload?.training;
                 // ignore if code already enclosed
-                if (code && closedCodesRef.current.has(code)) return;
+                if (!training && code && closedCodesRef.current.has(code)) return;
                 // de-dup: if this code is already active (no response yet), skip re-trigger
-                if (code && activeCodesRef.current.has(code)) return;
-                if (code) activeCodesRef.current.add(code);
-                // allow future response notification for this code (fresh trigger)
-                if (code) responseSentRef.current.delete(code);
-                // record trigger start time
-                if (code) triggerTimesRef.current.set(code, Date.now());
+                // (a re-alarm after a snooze, or a call held back while the page reloaded, is the exception)
+                const alreadyActive = !!code && activeCodesRef.current.has(code);
+                if (!training && alreadyActive && !e.payload?.realarm && !e.payload?.queued) return;
+                if (!training && code && !alreadyActive) {
+                    activeCodesRef.current.add(code);
+                    // allow future response notification for this code (fresh trigger)
+                    responseSentRef.current.delete(code);
+                    // record trigger start time
+                    triggerTimesRef.current.set(code, Date.now());
+                }
+                // snoozed: tracked as active, but no sound or notifications until re-alarm
+                if (e.payload?.snoozed) return;
                 enqueueSounds(code, files);
                 (async () => {
                     const ok = await audioQueue.ensureUnlocked();
@@ -375,8 +406,10 @@ export default function () {
                 setTimeout(() => setStandbyStatus('green'), 1000);
                 const disp = e.payload?.display || (e.payload?.room ? `${e.payload?.room} - ${e.payload?.bed || ''}`.trim() : e.payload?.code) || 'NURSE CALL';
                 // use fixed ID so duplicate listens update the same notif instead of adding a new one
-                notifications.show({ id: `NC_${code}`, title: 'Nurse Call', message: disp });
-                try { osNotify('NURSE CALL', disp); } catch {}
+                notifications.show({ id: `NC_${code}`, title: training ? 'Nurse Call (TRAINING)' : 'Nurse Call', message: disp, color: training ? 'grape' : undefined });
+                try { osNotify(training ? 'NURSE CALL (TRAINING)' : 'NURSE CALL', disp); } catch {}
+                // training calls (sent to this window only) are never forwarded
+                if (training) return;
                 try {
                     const msg = buildTriggerMessage(e.payload?.room, e.payload?.bed, new Date());
                     void sendTelegram(msg);
@@ -385,6 +418,23 @@ export default function () {
             return () => { if (unlisten) unlisten(); };
         }, []);
 
+        // muted codes/rooms: recorded and shown like any call, but no sound, OS toast or Telegram
+        useEffect(() => {
+            let unlisten: any;
+            listen<{ code: string, display?: string, room?: string, bed?: string, queued?: boolean }>('nurse-call-muted', (e) => {
+                if (!serialConnectedRef.current && !e.payload?.queued) return;
+                const code = String(e.payload?.code || '');
+                if (code && !activeCodesRef.current.has(code)) {
+                    activeCodesRef.current.add(code);
+                    responseSentRef.current.delete(code);
+                    triggerTimesRef.current.set(code, Date.now());
+                }
+                const disp = e.payload?.display || e.payload?.code || 'NURSE CALL';
+                notifications.show({ id: `NC_${code}`, title: 'Nurse Call (MUTED)', message: disp, color: 'gray' });
+            }).then((u) => unlisten = u);
+            return () => { if (unlisten) unlisten(); };
+        }, []);
+
         // keep standby indicator in sync with backend serial events
         useEffect(() => {
             let un1: any, un2: any;
@@ -425,13 +475,10 @@ export default function () {
                         // fallback to persisted config if no in-memory timestamp
                         let startedAt: string | undefined;
                         try {
-                            const cfg = await fetch('/config.json?ts=' + Date.now());
-                            if (cfg.ok) {
-                                const json = await cfg.json();
-                                const arr: any[] = json?.callHistoryStorage || [];
-                                const last = arr.slice().reverse().find(r => String(r.code || '') === code);
-                                if (last) startedAt = String(last.timestamp || last.callTime || '');
-                            }
+                            const json: any = await invoke('config_read');
+                            const arr: any[] = json?.callHistoryStorage || [];
+                            const last = arr.slice().reverse().find(r => String(r.code || '') === code);
+                            if (last) startedAt = String(last.timestamp || last.callTime || '');
                         } catch {}
                         if (startedAt) {
                             try { dur = Math.max(0, Math.round((end.getTime() - new Date(startedAt).getTime()) / 1000)); } catch {}
@@ -449,6 +496,31 @@ export default function () {
             }).then(u => un = u);
             return () => { if (un) un(); };
         }, []);
+
+        // after the listeners above: settle the connection state, pick up calls still open from
+        // before this (re)load, then let the backend re-deliver events it held while no page was listening
+        useEffect(() => {
+            (async () => {
+                try {
+                    const status: any = await invoke('serial_status');
+                    if (status?.connected) { serialConnectedRef.current = true; setStandbyStatus('green'); }
+                } catch (e) { console.error('serial_status failed', e); }
+                try {
+                    const open = await invoke<{ code: string, display?: string, muted?: boolean, timestampMs?: number }[]>('nurse_call_sync');
+                    for (const call of open) {
+                        const code = String(call.code || '');
+                        if (!code) continue;
+                        // synced calls are recorded, so they are shown regardless of the connection gate
+                        if (!activeCodesRef.current.has(code)) {
+                            notifications.show({ id: `NC_${code}`, title: call.muted ? 'Nurse Call (MUTED)' : 'Nurse Call', message: call.display || code, color: call.muted ? 'gray' : undefined });
+                        }
+                        activeCodesRef.current.add(code);
+                        if (typeof call.timestampMs === 'number' && !triggerTimesRef.current.has(code)) triggerTimesRef.current.set(code, call.timestampMs);
+                    }
+                } catch (e) { console.error('nurse_call_sync failed', e); }
+                await tauriEvent.emit('frontend-ready', { label: getCurrentWebviewWindow().label });
+            })().catch(console.error);
+        }, []);
 	}
 
 	function NavLinks() {
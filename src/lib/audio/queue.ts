@@ -1,11 +1,22 @@
 type Stack = { code?: string; files: string[] };
 
+export type AudioOutput = { deviceId: string; label: string };
+
+// audio output devices the webview can play to; labels may be empty until media permission is granted
+export async function listAudioOutputs(): Promise<AudioOutput[]> {
+  if (!navigator.mediaDevices?.enumerateDevices) return [];
+  const devices = await navigator.mediaDevices.enumerateDevices();
+  return devices.filter(d => d.kind === 'audiooutput').map(d => ({ deviceId: d.deviceId, label: d.label }));
+}
+
 class AudioQueue {
   private ctx: AudioContext | null = null;
   private gain: GainNode | null = null;
   private cache = new Map<string, AudioBuffer>();
   private playing = false;
   private stacks: Stack[] = [];
+  // set while playback is pinned to a device: the gain feeds this element instead of ctx.destination
+  private sink: HTMLAudioElement | null = null;
 
   init() {
     if (this.ctx) return;
@@ -24,6 +35,8 @@ class AudioQueue {
     if (c.state === 'running') return true;
     try {
       await c.resume();
+      // a pinned output's element is blocked by the same autoplay policy until a gesture
+      if (this.sink) await this.sink.play().catch(() => {});
       return c.state === 'running';
     } catch {
       return false;
@@ -35,6 +48,40 @@ class AudioQueue {
     this.gain.gain.value = Math.max(0, Math.min(1, v));
   }
 
+  // Plays to the output labelled `name` (null: the system default) through an <audio> element,
+  // since HTMLMediaElement.setSinkId is how the webview selects a device. Throws if the device is
+  // gone or can't be selected; playback is back on the default output by then.
+  async setOutput(name: string | null) {
+    if (!this.ctx) this.init();
+    const c = this.ctx;
+    const g = this.gain;
+    if (!c || !g) return;
+    const toDefault = () => {
+      try { g.disconnect(); } catch {}
+      g.connect(c.destination);
+      if (this.sink) { this.sink.pause(); this.sink.srcObject = null; this.sink = null; }
+    };
+    if (!name) { toDefault(); return; }
+    const device = (await listAudioOutputs()).find(d => d.label === name);
+    const el = this.sink || new Audio();
+    try {
+      if (!device) throw new Error(`audio output "${name}" not found`);
+      if (typeof (el as any).setSinkId !== 'function') throw new Error('this webview cannot select an audio output');
+      await (el as any).setSinkId(device.deviceId);
+    } catch (e) {
+      toDefault();
+      throw e;
+    }
+    if (!this.sink) {
+      const dest = c.createMediaStreamDestination();
+      try { g.disconnect(); } catch {}
+      g.connect(dest);
+      el.srcObject = dest.stream;
+      this.sink = el;
+    }
+    try { await el.play(); } catch {}
+  }
+
   async preload(names: string[]) {
     const unique = Array.from(new Set(names.filter(Boolean)));
     for (const name of unique) {
@@ -4,7 +4,7 @@ import { useEffect } from 'react';
 import dayjs from 'dayjs';
 import { isTauri } from '@tauri-apps/api/core';
 import * as fs from '@tauri-apps/plugin-fs';
-import { message } from '@tauri-apps/plugin-dialog';
+import { message, save } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
 import { join, sanitizeFilename, downloadFile } from '../common/utils';
 import { buildSimplePdf } from '../common/pdf';
@@ -13,7 +13,8 @@ import { createStorage } from '../tauri/storage';
 import classes from './CallHistory.module.css';
 import { upsertFromLegacy, readHistoryV2, softDeleteRange } from '../lib/history/store';
 
-type Status = 'active' | 'completed';
+type Status = 'active' | 'acknowledged' | 'completed';
+const statusLabel = (s: Status) => (s === 'active' ? 'Active' : s === 'acknowledged' ? 'Acknowledged' : 'Completed');
 type CallRecord = {
   id: string;
   code: string;
@@ -43,7 +44,7 @@ export default function CallHistory() {
     const inTo = to ? d.isBefore(dayjs(to).endOf('day')) || d.isSame(dayjs(to).endOf('day')) : true;
     return inFrom && inTo;
   });
-  const activeCount = filtered.filter(r => r.status === 'active').length;
+  const activeCount = filtered.filter(r => r.status !== 'completed').length;
   const totalCount = filtered.length;
 
   function fmt(ts?: string) {
@@ -88,7 +89,7 @@ export default function CallHistory() {
       { h: 'Call Time', w: 19, get: (r: CallRecord) => fmt(r.callTime) },
       { h: 'Response', w: 19, get: (r: CallRecord) => fmt(r.responseTime) },
       { h: 'Durasi', w: 8, get: (r: CallRecord) => duration(r.callTime, r.responseTime) },
-      { h: 'Status', w: 9, get: (r: CallRecord) => (r.status === 'active' ? 'Active' : 'Completed') },
+      { h: 'Status', w: 9, get: (r: CallRecord) => statusLabel(r.status) },
     ];
     function fixWidth(text: string, width: number) {
       const s = (text ?? '').toString();
@@ -174,7 +175,7 @@ export default function CallHistory() {
       bedName: String(x.bed ?? x.bedName ?? ''),
       callTime: String(x.timestamp ?? x.callTime ?? ''),
       responseTime: x.resetTime ?? x.responseTime,
-      status: (x.status === 'active' ? 'active' : 'completed') as Status,
+      status: (x.status === 'active' || x.status === 'acknowledged' ? x.status : 'completed') as Status,
     }));
   }
 
@@ -207,8 +208,7 @@ export default function CallHistory() {
           const inTo = end ? (d.isBefore(end) || d.isSame(end)) : true;
           return !(inFrom && inTo);
         });
-        const out = { ...json, callHistoryStorage: nextRaw };
-        await invoke('write_public_config', { text: JSON.stringify(out, null, 2) });
+        await invoke('update_config', { patch: { callHistoryStorage: nextRaw } });
         // Also mark soft-deleted in V2 for audit
         try { await softDeleteRange(from || undefined, to || undefined, 'range-clear'); } catch {}
         try { await message(`History cleared${start || end ? ' for selected date range' : ''}.`, { title: 'Call History' }); } catch {}
@@ -244,6 +244,18 @@ export default function CallHistory() {
     }
   }
 
+  async function exportCsv() {
+    if (!isTauri()) return;
+    try {
+      const path = await save({ defaultPath: `call-history-${dayjs().format('YYYY-MM-DD')}.csv`, filters: [{ name: 'CSV', extensions: ['csv'] }] });
+      if (!path) return;
+      const rows = await invoke<number>('export_call_history_csv', { path });
+      await message(`${rows} records exported to ${path}`, { title: 'Call History' });
+    } catch (e) {
+      await message(`CSV export failed: ${e}`, { title: 'Call History', kind: 'error' });
+    }
+  }
+
   // initial + periodic sync (every 5s) — wait until storage finished loading
   useEffect(() => {
     if (loading) return;
@@ -276,6 +288,7 @@ export default function CallHistory() {
         </label>
         <Group gap="xs">
           <Button size="xs" onClick={printReport}>Print Report</Button>
+          {isTauri() && <Button size="xs" variant="light" onClick={exportCsv}>Export CSV</Button>}
           <Button size="xs" variant="light" onClick={() => { setFrom(() => ''); setTo(() => ''); }}>Clear Filter</Button>
           {import.meta.env.DEV && <Button size="xs" color="red" variant="light" onClick={clearHistory}>Clear History</Button>}
         </Group>
@@ -308,8 +321,8 @@ export default function CallHistory() {
                     <td>{fmt(rec.responseTime)}</td>
                     <td>{duration(rec.callTime, rec.responseTime)}</td>
                     <td>
-                      <span className={`${classes.statusBadge} ${rec.status === 'active' ? classes.active : classes.completed}`}>
-                        {rec.status === 'active' ? 'Active' : 'Completed'}
+                      <span className={`${classes.statusBadge} ${rec.status === 'completed' ? classes.completed : classes.active}`}>
+                        {statusLabel(rec.status)}
                       </span>
                     </td>
                   </tr>
@@ -348,7 +361,7 @@ export default function CallHistory() {
                 <td>{fmt(rec.callTime)}</td>
                 <td>{fmt(rec.responseTime)}</td>
                 <td>{duration(rec.callTime, rec.responseTime)}</td>
-                <td>{rec.status === 'active' ? 'Active' : 'Completed'}</td>
+                <td>{statusLabel(rec.status)}</td>
               </tr>
             ))}
           </tbody>
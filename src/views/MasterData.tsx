@@ -4,7 +4,7 @@ import { notifications } from '@mantine/notifications';
 import { useEffect, useMemo, useState } from 'react';
 import { downloadFile } from '../common/utils';
 import classes from './MasterData.module.css';
-import { isTauri } from '@tauri-apps/api/core';
+import { invoke, isTauri } from '@tauri-apps/api/core';
 import * as fs from '@tauri-apps/plugin-fs';
 import * as devmode from '../lib/devmode';
 
@@ -29,17 +29,21 @@ export default function MasterData() {
   const [unlocked, setUnlocked] = useState(false);
   const [password, setPassword] = useState('');
 
-  // Absolute paths for local dev save/import (Tauri)
+  // Absolute path for local dev sound import (Tauri)
   const PROJECT_PUBLIC = '/Users/maul/github/modern-desktop-app-template/public';
-  const CONFIG_PATH = `${PROJECT_PUBLIC}/config.json`;
   const SOUND_DIR = `${PROJECT_PUBLIC}/sounds`;
 
-  // load config.json from public
+  // load config.json from the backend (same file the serial worker uses); public/ in a browser
   async function loadConfig() {
     try {
-      const res = await fetch('/config.json?_=' + Date.now());
-      if (!res.ok) return;
-      const json: Config = await res.json();
+      let json: Config;
+      if (isTauri()) {
+        json = await invoke<Config>('config_read');
+      } else {
+        const res = await fetch('/config.json?_=' + Date.now());
+        if (!res.ok) return;
+        json = await res.json();
+      }
       setConfig(json);
       setRows((json.masterData || []).map(r => ({ ...r })));
     } catch (e) {
@@ -95,7 +99,8 @@ export default function MasterData() {
     const next: Config = { ...config, masterData: rows };
     const blob = JSON.stringify(next, null, 2);
     if (isTauri()) {
-      fs.writeTextFile(CONFIG_PATH, blob)
+      // merge only masterData so call history written meanwhile is kept
+      invoke('update_config', { patch: { masterData: rows } })
         .then(async () => { await loadConfig(); notifications.show({ title: 'Master Data', message: 'Perubahan tersimpan ke config.json', color: 'teal' }); })
         .catch(e => notifications.show({ title: 'Gagal menyimpan', message: String(e), color: 'red' }));
     } else {
@@ -3,7 +3,6 @@ import { notifications } from '@mantine/notifications';
 import { useEffect, useState } from 'react';
 import { isTauri, invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import * as fs from '@tauri-apps/plugin-fs';
 import { Command } from '@tauri-apps/plugin-shell';
 import classes from './MasterSettings.module.css';
 
@@ -14,6 +13,7 @@ type MasterSettingsCfg = {
   bot?: string;
   idChat?: string;
   server?: string;
+  audioOutput?: string;
 }
 
 type Config = {
@@ -21,8 +21,6 @@ type Config = {
   [k: string]: any;
 }
 
-const PROJECT_PUBLIC = '/Users/maul/github/modern-desktop-app-template/public';
-const CONFIG_PATH = `${PROJECT_PUBLIC}/config.json`;
 
 export default function MasterSettings() {
   const [cfg, setCfg] = useState<MasterSettingsCfg>({});
@@ -31,16 +29,36 @@ export default function MasterSettings() {
   const [connected, setConnected] = useState(false);
   const [monitor, setMonitor] = useState('');
   const [proc, setProc] = useState<any>(null);
+  const [outputs, setOutputs] = useState<string[]>([]);
 
+  // the backend's config.json (same file the serial worker uses); public/ in a browser
   async function loadConfig() {
     try {
-      const res = await fetch('/config.json?ts=' + Date.now());
-      if (!res.ok) return;
-      const json: Config = await res.json();
+      let json: Config;
+      if (isTauri()) {
+        json = await invoke<Config>('config_read');
+      } else {
+        const res = await fetch('/config.json?ts=' + Date.now());
+        if (!res.ok) return;
+        json = await res.json();
+      }
       setCfg(json.masterSettings || {});
     } catch (e) {
       console.error(e);
     }
+    if (isTauri()) {
+      try {
+        const report = await invoke<{ valid: boolean; duplicates: string[]; missing: { index: number; charCode: string; fields: string[] }[] }>('validate_master_data');
+        if (!report.valid) {
+          const parts: string[] = [];
+          if (report.duplicates.length) parts.push(`Duplicate codes: ${report.duplicates.join(', ')}`);
+          if (report.missing.length) parts.push(`${report.missing.length} entries missing required fields`);
+          notifications.show({ color: 'yellow', title: 'Master Data', message: parts.join('. ') });
+        }
+      } catch (e) {
+        console.error('validate_master_data', e);
+      }
+    }
   }
 
   useEffect(() => { loadConfig(); }, []);
@@ -48,17 +66,26 @@ export default function MasterSettings() {
   useEffect(() => {
     if (!isTauri()) return;
     let un1: any, un2: any, un3: any;
-    listen<string>('serial-connected', (e) => {
+    listen<{ port?: string, downtimeMs?: number }>('serial-connected', (e) => {
       setConnected(true);
-      setMonitor(m => m + `\n[${new Date().toLocaleTimeString()}] Connected to ${e.payload || cfg.com}`);
+      const down = e.payload?.downtimeMs ? ` (recovered after ${Math.round(e.payload.downtimeMs / 1000)}s)` : '';
+      setMonitor(m => m + `\n[${new Date().toLocaleTimeString()}] Connected to ${e.payload?.port || cfg.com}${down}`);
     }).then(u => un1 = u);
-    listen('serial-disconnected', () => {
+    listen<{ port?: string, reason?: string }>('serial-disconnected', (e) => {
       setConnected(false);
-      setMonitor(m => m + `\n[${new Date().toLocaleTimeString()}] Disconnected.`);
+      const reason = e.payload?.reason;
+      setMonitor(m => m + `\n[${new Date().toLocaleTimeString()}] Disconnected${reason ? ` (${reason})` : ''}.`);
     }).then(u => un2 = u);
-    listen<string>('serial-error', (e) => {
+    listen<{ kind: string, port: string, message: string, retrying: boolean }>('serial-error', (e) => {
+      const { kind, message, retrying } = e.payload;
+      const time = new Date().toLocaleTimeString();
+      if (kind === 'config-missing' || kind === 'config-parse') {
+        setMonitor(m => m + `\n[${time}] Config error: ${message}. Check the config file.`);
+        return;
+      }
       setConnected(false);
-      setMonitor(m => m + `\n[${new Date().toLocaleTimeString()}] Connect error: ${e.payload || 'busy'}. Retrying...`);
+      const hint = kind === 'open-failed' ? ' Unplug and replug the adapter if this persists.' : '';
+      setMonitor(m => m + `\n[${time}] ${kind === 'read-failed' ? 'Read' : 'Connect'} error: ${message || 'busy'}.${retrying ? ' Retrying...' : ''}${hint}`);
     }).then(u => un3 = u);
     return () => { if (un1) un1(); if (un2) un2(); if (un3) un3(); };
   }, [cfg.com]);
@@ -145,16 +172,33 @@ export default function MasterSettings() {
     notifications.show({ title: 'Hardware Disconnected', message: 'Hardware has been disconnected.', color: 'gray' });
   }
 
+  async function refreshOutputs() {
+    if (!isTauri()) return;
+    try { setOutputs(await invoke<string[]>('list_audio_outputs')); } catch (e) { console.error('list_audio_outputs', e); }
+  }
+  useEffect(() => { refreshOutputs(); }, []);
+
+  // applied and saved at once; a device that has gone is reported and the default is used
+  async function pickOutput(name: string | null) {
+    try {
+      await invoke('set_audio_output', { name });
+      setCfg(s => ({ ...s, audioOutput: name || undefined }));
+    } catch (e) {
+      setCfg(s => ({ ...s, audioOutput: undefined }));
+      notifications.show({ title: 'Audio Output', message: String(e), color: 'red' });
+    }
+  }
+
   async function save() {
     try {
-      const res = await fetch('/config.json?ts=' + Date.now());
-      const json: Config = res.ok ? await res.json() : {};
-      json.masterSettings = cfg;
-      const blob = JSON.stringify(json, null, 2);
       if (isTauri()) {
-        await fs.writeTextFile(CONFIG_PATH, blob);
+        await invoke('update_config', { patch: { masterSettings: cfg } });
         notifications.show({ title: 'Master Settings', message: 'Tersimpan ke config.json', color: 'teal' });
       } else {
+        const res = await fetch('/config.json?ts=' + Date.now());
+        const json: Config = res.ok ? await res.json() : {};
+        json.masterSettings = cfg;
+        const blob = JSON.stringify(json, null, 2);
         const link = document.createElement('a');
         link.href = URL.createObjectURL(new Blob([blob], { type: 'application/json' }));
         link.download = 'config.json';
@@ -190,6 +234,7 @@ export default function MasterSettings() {
         <div className={classes.panel}>
           <TextInput label="Name" value={cfg.name || ''} onChange={e => setCfg(s => ({ ...s, name: e.currentTarget.value }))} />
           <Select label="Master" data={[{value:'Commax',label:'Commax'},{value:'AIPHONE',label:'AIPHONE'}]} value={cfg.masterType || 'Commax'} onChange={(v) => setCfg(s => ({ ...s, masterType: (v as any) }))} mt="sm" />
+          <Select label="Audio Output" placeholder="Default" data={outputs} value={cfg.audioOutput || null} onChange={pickOutput} onDropdownOpen={refreshOutputs} disabled={!isTauri()} clearable mt="sm" />
           <TextInput label="Bot" value={cfg.bot || ''} onChange={e => setCfg(s => ({ ...s, bot: e.currentTarget.value }))} mt="sm" />
           <TextInput label="ID Chat" value={cfg.idChat || ''} onChange={e => setCfg(s => ({ ...s, idChat: e.currentTarget.value }))} mt="sm" />
           <TextInput label="Server" placeholder="Server" value={cfg.server || ''} onChange={e => setCfg(s => ({ ...s, server: e.currentTarget.value }))} mt="sm" />